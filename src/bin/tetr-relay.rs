@@ -0,0 +1,286 @@
+//! The relay server for online matches.
+//!
+//! Clients connect, join a room by name and are paired with the next client joining the same room.
+//! The relay then hands both of them the same seed and forwards the garbage, snapshots and top
+//! outs of one of them to the other one. The games themselves are simulated by the clients.
+//!
+//! Usage: `tetr-relay [address]`, the default address is `0.0.0.0:7777`
+
+#[allow(dead_code, unused_imports)]
+#[path = "../net/mod.rs"]
+mod net;
+
+use net::protocol::*;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+struct Peer {
+    id: u64,
+    name: String,
+    stream: TcpStream,
+}
+
+/// The longest line a client may send, clients sending longer lines are dropped
+const MAX_LINE: u64 = 64 * 1024;
+
+/// Players in every room, a room holds at most two players
+type Rooms = Arc<Mutex<HashMap<String, Vec<Peer>>>>;
+
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_PORT));
+    let listener = TcpListener::bind(&address).expect("Unable to bind the relay address");
+    println!("Relay listening on {}", address);
+    accept(listener);
+}
+
+/// Serve every client connecting to the listener on its own thread
+fn accept(listener: TcpListener) {
+    let rooms = Rooms::default();
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Connection failed: {}", e);
+                continue;
+            }
+        };
+        let rooms = rooms.clone();
+        std::thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            if let Err(e) = serve(id as u64, stream, rooms) {
+                eprintln!("Client {:?} disconnected: {}", peer, e);
+            }
+        });
+    }
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> std::io::Result<()> {
+    stream.write_all(message.encode().as_bytes())
+}
+
+/// Read a single line of at most [`MAX_LINE`] bytes into `line`, replacing its contents
+fn read_line(reader: &mut BufReader<TcpStream>, line: &mut String) -> std::io::Result<usize> {
+    line.clear();
+    let n = reader.by_ref().take(MAX_LINE).read_line(line)?;
+    if n as u64 == MAX_LINE && !line.ends_with('\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "line too long",
+        ));
+    }
+    Ok(n)
+}
+
+/// The streams of the other players in the room, cloned so that they are written to without
+/// holding the lock, a stalled client would block every room otherwise
+fn others(rooms: &Rooms, room: &str, id: u64) -> Vec<TcpStream> {
+    let rooms = rooms.lock().unwrap();
+    rooms
+        .get(room)
+        .into_iter()
+        .flatten()
+        .filter(|x| x.id != id)
+        .filter_map(|x| x.stream.try_clone().ok())
+        .collect()
+}
+
+/// Handle a single client until it disconnects
+fn serve(id: u64, mut stream: TcpStream, rooms: Rooms) -> std::io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let (room, name) = match Message::decode(&line) {
+        Some(Message::Join {
+            version: PROTOCOL_VERSION,
+            room,
+            name,
+        }) => (room, name),
+        Some(Message::Join { .. }) => {
+            return send(
+                &mut stream,
+                &Message::Error(String::from("incompatible game version")),
+            );
+        }
+        _ => return send(&mut stream, &Message::Error(String::from("expected join"))),
+    };
+
+    let starts = {
+        let mut rooms = rooms.lock().unwrap();
+        let peers = rooms.entry(room.clone()).or_default();
+        if peers.len() >= 2 {
+            return send(
+                &mut stream,
+                &Message::Error(String::from("the room is full")),
+            );
+        }
+        peers.push(Peer {
+            id,
+            name: name.clone(),
+            stream: stream.try_clone()?,
+        });
+        println!("{} joined room {}", name, room);
+
+        let mut starts = Vec::new();
+        if peers.len() == 2 {
+            let seed = rand::random::<u64>();
+            for i in 0..2 {
+                let opponent = peers[1 - i].name.clone();
+                starts.push((
+                    peers[i].stream.try_clone()?,
+                    Message::Start { seed, opponent },
+                ));
+            }
+        }
+        starts
+    };
+    for (mut stream, message) in starts {
+        // a failed write shows up as a disconnect in the thread of that peer
+        let _ = send(&mut stream, &message);
+    }
+
+    // forward what happens in the game to the other player in the room, the other messages are
+    // only sent by the relay
+    let result = loop {
+        match read_line(&mut reader, &mut line) {
+            Ok(0) => break Ok(()),
+            Ok(_) => (),
+            Err(e) => break Err(e),
+        }
+        if !matches!(
+            Message::decode(&line),
+            Some(Message::Garbage(_) | Message::Snapshot(_) | Message::ToppedOut)
+        ) {
+            continue;
+        }
+        for mut stream in others(&rooms, &room, id) {
+            let _ = stream.write_all(line.as_bytes());
+        }
+    };
+
+    let left = others(&rooms, &room, id);
+    {
+        let mut rooms = rooms.lock().unwrap();
+        if let Some(peers) = rooms.get_mut(&room) {
+            peers.retain(|x| x.id != id);
+            if peers.is_empty() {
+                rooms.remove(&room);
+            }
+        }
+    }
+    for mut stream in left {
+        let _ = send(&mut stream, &Message::Left);
+    }
+    println!("{} left room {}", name, room);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net::Client;
+    use std::time::{Duration, Instant};
+
+    /// Poll the client until the relay says something
+    fn receive(client: &mut Client) -> Vec<Message> {
+        let start = Instant::now();
+        loop {
+            let messages = client.poll().unwrap();
+            if !messages.is_empty() {
+                return messages;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "the relay didn't answer"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn relay() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || accept(listener));
+        address.to_string()
+    }
+
+    /// Connect two clients to a room and wait for the match to start
+    fn pair(address: &str) -> (Client, Client) {
+        let mut a = Client::connect(address, "room", "a").unwrap();
+        let mut b = Client::connect(address, "room", "b").unwrap();
+        // the join of b is sent on its first poll
+        b.poll().unwrap();
+
+        match (&receive(&mut a)[..], &receive(&mut b)[..]) {
+            (
+                [Message::Start {
+                    seed: x,
+                    opponent: a_opponent,
+                }],
+                [Message::Start {
+                    seed: y,
+                    opponent: b_opponent,
+                }],
+            ) => {
+                assert_eq!((a_opponent.as_str(), b_opponent.as_str()), ("b", "a"));
+                assert_eq!(x, y);
+            }
+            x => panic!("expected both players to start, got {:?}", x),
+        }
+        (a, b)
+    }
+
+    #[test]
+    fn pairs_and_forwards_on_localhost() {
+        let address = relay();
+        let (mut a, mut b) = pair(&address);
+
+        // a third player is refused
+        let mut c = Client::connect(&address, "room", "c").unwrap();
+        assert!(matches!(&receive(&mut c)[..], [Message::Error(_)]));
+
+        // only what happens in the game is forwarded
+        a.send(&Message::Start {
+            seed: 0,
+            opponent: String::from("x"),
+        });
+        a.send(&Message::Error(String::from("x")));
+        a.send(&Message::Left);
+        a.send(&Message::Garbage(4));
+        a.poll().unwrap();
+        assert!(matches!(&receive(&mut b)[..], [Message::Garbage(4)]));
+
+        b.send(&Message::ToppedOut);
+        b.poll().unwrap();
+        assert!(matches!(&receive(&mut a)[..], [Message::ToppedOut]));
+
+        drop(a);
+        assert!(matches!(&receive(&mut b)[..], [Message::Left]));
+    }
+
+    #[test]
+    fn drops_clients_sending_long_lines() {
+        let address = relay();
+        let (mut a, mut b) = pair(&address);
+
+        let long = "x".repeat(MAX_LINE as usize);
+        a.send(&Message::Snapshot(json::object! { blocks: long.as_str() }));
+        // the line is sent over several polls
+        let start = Instant::now();
+        let messages = loop {
+            let _ = a.poll();
+            let messages = b.poll().unwrap();
+            if !messages.is_empty() {
+                break messages;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "a wasn't dropped");
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert!(matches!(&messages[..], [Message::Left]));
+    }
+}
@@ -3,6 +3,22 @@ use glam::{Mat4, Vec2, Vec3, Vec4};
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// The directory with the pictures shown behind the game
+const BACKGROUNDS_DIR: &str = "backgrounds";
+
+/// The files in the backgrounds directory, sorted by name
+pub fn wallpapers() -> Vec<String> {
+    let mut files = match std::fs::read_dir(BACKGROUNDS_DIR) {
+        Ok(dir) => dir
+            .filter_map(|x| x.ok())
+            .filter_map(|x| x.file_name().into_string().ok())
+            .collect::<Vec<String>>(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
 struct Triangle {
     position: Vec2,
     scale: f32,
@@ -37,6 +53,7 @@ impl Background {
     pub fn new(
         gh: &mut crate::graphics::GraphicsHandle,
         roman: &crate::resource::ResourceManager,
+        wallpaper: &crate::settings::Wallpaper,
     ) -> Self {
         let vert = roman.get_text("default.vert");
         let frag = roman.get_text("solid_color.frag");
@@ -55,7 +72,7 @@ impl Background {
             triangles.push(Triangle::new());
         }
 
-        let background = Self::pick_wapllpaper(roman);
+        let background = Self::pick_wapllpaper(roman, wallpaper);
         let background_aspect = background.width() as f32 / background.height() as f32;
         let background = graphics::Texture::from_image(gh, &background).unwrap();
 
@@ -163,35 +180,36 @@ impl Background {
 
     fn pick_wapllpaper(
         roman: &crate::resource::ResourceManager,
+        wallpaper: &crate::settings::Wallpaper,
     ) -> Rc<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
-        let backgrounds_dir = "backgrounds";
         let nobackgrounds = roman.get_image("no_background.png");
-        if let Ok(dir) = std::fs::read_dir(backgrounds_dir) {
-            let files = dir
-                .map(|x| x.unwrap().file_name().into_string().unwrap())
-                .collect::<Vec<String>>();
-            if files.len() != 0 {
-                let file = format!(
-                    "{}/{}",
-                    backgrounds_dir,
-                    files[rand::random::<usize>() % files.len()]
-                );
-
-                Rc::new(
-                    image::io::Reader::new(std::io::BufReader::new(
-                        std::fs::File::open(file).unwrap(),
-                    ))
+        let file = match wallpaper {
+            crate::settings::Wallpaper::None => return nobackgrounds,
+            crate::settings::Wallpaper::File(x) => x.clone(),
+            crate::settings::Wallpaper::Random => {
+                let files = wallpapers();
+                if files.is_empty() {
+                    return nobackgrounds;
+                }
+                files[rand::random::<usize>() % files.len()].clone()
+            }
+        };
+
+        let image = std::fs::File::open(format!("{}/{}", BACKGROUNDS_DIR, file))
+            .map_err(|e| e.to_string())
+            .and_then(|x| {
+                image::io::Reader::new(std::io::BufReader::new(x))
                     .with_guessed_format()
-                    .expect("Unrecognized background file format!")
+                    .map_err(|e| e.to_string())?
                     .decode()
-                    .expect("unable to load background image")
-                    .into_rgba8(),
-                )
-            } else {
+                    .map_err(|e| e.to_string())
+            });
+        match image {
+            Ok(x) => Rc::new(x.into_rgba8()),
+            Err(e) => {
+                eprintln!("Unable to load background {}: {}", file, e);
                 nobackgrounds
             }
-        } else {
-            nobackgrounds
         }
     }
 }
@@ -0,0 +1,57 @@
+use super::Event;
+use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
+use std::{cell::RefCell, rc::Rc};
+
+/// Plays the sounds of a board in response to its events
+pub struct BoardAudio {
+    audio: Rc<RefCell<kira::manager::AudioManager>>,
+
+    clear_sound: StaticSoundData,
+    drop_sound: StaticSoundData,
+}
+
+impl BoardAudio {
+    pub fn new(
+        audio: Rc<RefCell<kira::manager::AudioManager>>,
+        roman: &crate::resource::ResourceManager,
+    ) -> Self {
+        let clear_sound = StaticSoundData::from_cursor(
+            std::io::Cursor::new((*roman.get_binary("clear.wav")).clone()),
+            StaticSoundSettings::default(),
+        )
+        .unwrap();
+
+        let drop_sound = StaticSoundData::from_cursor(
+            std::io::Cursor::new((*roman.get_binary("drop.wav")).clone()),
+            StaticSoundSettings::default(),
+        )
+        .unwrap();
+
+        Self {
+            audio,
+            clear_sound,
+            drop_sound,
+        }
+    }
+
+    pub fn handle(&mut self, event: &Event) {
+        match event {
+            Event::HardDropped { .. } => {
+                self.audio
+                    .borrow_mut()
+                    .play(self.drop_sound.clone())
+                    .unwrap();
+            }
+            Event::LinesCleared { combo, .. } => {
+                self.audio
+                    .borrow_mut()
+                    .play(self.clear_sound.clone().with_modified_settings(|_| {
+                        StaticSoundSettings::new()
+                            .playback_rate(kira::PlaybackRate::Semitones(*combo as _))
+                    }))
+                    .unwrap();
+            }
+            _ => (),
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use super::Event;
+use crate::game::block::Block;
+use crate::game::tetromino::Tetromino;
 use glam::{Vec2, Vec4};
 
 pub struct InfoText {
@@ -68,6 +71,17 @@ pub struct BoardEffects {
     pub particles: Vec<Particle>,
 
     pub info: Option<InfoText>,
+
+    /// The time when the board has topped out
+    pub death_time: Option<std::time::Instant>,
+    /// Keep the board on the screen greyed out after it has topped out, instead of dropping it
+    pub grey_out: bool,
+    /// Whether to throw particles around
+    pub show_particles: bool,
+    /// Whether the board bounces and shakes
+    pub motion: bool,
+    /// The number of columns of the board, for the particles of cleared lines
+    pub width: usize,
 }
 
 impl BoardEffects {
@@ -83,10 +97,147 @@ impl BoardEffects {
             scale_friction,
             particles: Vec::new(),
             info: None,
+            death_time: None,
+            grey_out: false,
+            show_particles: true,
+            motion: true,
+            width: 10,
+        }
+    }
+
+    /// Move the timers by `duration`, as if everything had happened later. Used after pausing.
+    pub fn delay(&mut self, duration: std::time::Duration) {
+        if let Some(x) = &mut self.info {
+            x.time += duration;
+        }
+        if let Some(x) = &mut self.death_time {
+            *x += duration;
+        }
+    }
+
+    /// Forget everything that has happened, but keep the options
+    pub fn reset(&mut self) {
+        *self = Self {
+            grey_out: self.grey_out,
+            show_particles: self.show_particles,
+            motion: self.motion,
+            width: self.width,
+            ..Self::new(
+                self.spring,
+                self.friction,
+                self.scale_spring,
+                self.scale_friction,
+            )
+        };
+    }
+
+    /// React to something that happened on the board
+    pub fn handle(&mut self, event: &Event) {
+        match event {
+            Event::Shifted(dx) => self.velocity.x += 0.03 * *dx as f32,
+            Event::SoftDropped => self.velocity.y -= 0.01,
+            Event::HardDropped { from, to } => {
+                self.velocity.y -= 0.15;
+                let mut piece = from.clone();
+                while piece.position.y >= to.position.y {
+                    self.hard_drop_fly_particles(&piece);
+                    piece.position.y -= 1;
+                }
+            }
+            Event::PieceLocked(piece) => {
+                // add a little bump for landing the piece
+                self.velocity.y -= 0.075;
+                self.land_particles(piece);
+            }
+            Event::LinesCleared { rows, .. } => {
+                for y in rows {
+                    self.line_clear_particles(*y);
+                }
+            }
+            Event::Announced(text) => {
+                self.info = Some(InfoText {
+                    text: text.clone(),
+                    time: std::time::Instant::now(),
+                });
+            }
+            Event::GarbageSent(n) => self.velocity.y -= 0.1 * *n as f32,
+            Event::GarbageReceived(n) => self.velocity.y += 0.1 * *n as f32,
+            Event::ToppedOut => {
+                if self.death_time.is_none() {
+                    self.death_time = Some(std::time::Instant::now());
+                }
+            }
+            Event::KeyPressed | Event::FinesseFault => (),
+        }
+    }
+
+    fn hard_drop_fly_particles(&mut self, piece: &Tetromino) {
+        let shape = piece.get_shape();
+        for (x, column) in shape.iter().enumerate() {
+            for (y, block) in column.iter().enumerate() {
+                if let Block::Block { .. } = block {
+                    if rand::random::<u32>().is_multiple_of(10) {
+                        self.particles.push(Particle::new(
+                            Vec2::new(
+                                (piece.position.x + x as i32) as f32 + rand::random::<f32>(),
+                                (piece.position.y + y as i32) as f32 + rand::random::<f32>(),
+                            ),
+                            Vec2::new(0.0, 0.1 * rand::random::<f32>()),
+                            Vec2::new(0.0, 0.01),
+                            0.2 * rand::random::<f32>(),
+                            0.005,
+                            ParticleModel::random_color(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    fn land_particles(&mut self, piece: &Tetromino) {
+        let shape = piece.get_shape();
+        for (x, column) in shape.iter().enumerate() {
+            for (y, block) in column.iter().enumerate() {
+                if let Block::Block { .. } = block {
+                    self.particles.push(Particle::new(
+                        Vec2::new(
+                            (piece.position.x + x as i32) as f32 + rand::random::<f32>(),
+                            (piece.position.y + y as i32) as f32 + rand::random::<f32>(),
+                        ),
+                        Vec2::new(0.0, 0.0),
+                        Vec2::new(0.0, 0.001),
+                        0.3 * rand::random::<f32>(),
+                        0.005,
+                        ParticleModel::Star,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn line_clear_particles(&mut self, y: usize) {
+        for x in 0..self.width {
+            self.particles.push(Particle::new(
+                Vec2::new(
+                    x as f32 + rand::random::<f32>(),
+                    y as f32 + rand::random::<f32>(),
+                ),
+                Vec2::new((rand::random::<f32>() - 0.5) * 0.1, 0.1),
+                Vec2::new(0.0, -0.01),
+                0.4 * rand::random::<f32>(),
+                0.005,
+                ParticleModel::Star,
+            ));
         }
     }
 
     pub fn update(&mut self) {
+        if !self.motion {
+            self.velocity = Vec2::new(0.0, 0.0);
+        }
+        if !self.show_particles {
+            self.particles.clear();
+        }
         self.position += self.velocity;
         self.scale += self.beat;
 
@@ -0,0 +1,38 @@
+use crate::game::tetromino::Tetromino;
+
+/// Something that happened on the board during an update.
+///
+/// The board itself doesn't play sounds or spawn particles, it only reports what happened.
+/// The events are collected by [`super::Board::drain_events`] and handed to whoever is interested
+/// (the audio, the particle effects or the game routing garbage between boards).
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The falling piece was moved sideways by `dx` blocks
+    Shifted(i32),
+    /// The falling piece has moved one block down because of soft drop
+    SoftDropped,
+    /// The falling piece was dropped all the way down, from `from` to `to`
+    HardDropped { from: Tetromino, to: Tetromino },
+    /// A piece was placed on the board
+    PieceLocked(Tetromino),
+    /// A key has been pressed
+    KeyPressed,
+    /// Rows were removed from the board, `rows` are the indices at the time of clearing.
+    /// `garbage` of them were garbage, and the clear is worth `attack` lines before cancelling.
+    LinesCleared {
+        rows: Vec<usize>,
+        combo: u32,
+        garbage: u32,
+        attack: u32,
+    },
+    /// The piece has been placed with more key presses than needed
+    FinesseFault,
+    /// The move was special enough to be announced (e.g. "TETRIS")
+    Announced(String),
+    /// The board sends `n` lines of garbage to its opponent
+    GarbageSent(u32),
+    /// `n` lines of garbage were inserted at the bottom of the board
+    GarbageReceived(u32),
+    /// The board reached the top and the game is over for it
+    ToppedOut,
+}
@@ -0,0 +1,70 @@
+use super::*;
+use crate::game::rotation::Rotation;
+
+/// The least number of key presses which move a new piece from `start` to where `target` is, on
+/// an empty playing field of the size given by the rules. Holding a direction until the piece
+/// hits the wall counts as a single press.
+///
+/// Returns `None` when the place can't be reached without dropping the piece first.
+pub fn optimal_inputs(target: &Tetromino, start: Tetromino, rules: &Rules) -> Option<u32> {
+    let field = empty_field(rules);
+    let goal = footprint(target);
+
+    let mut seen = vec![(start.position.x, start.rotation_state())];
+    let mut queue = std::collections::VecDeque::from([(start, 0)]);
+    while let Some((piece, inputs)) = queue.pop_front() {
+        if footprint(&piece) == goal {
+            return Some(inputs);
+        }
+        for next in moves(&piece, &field) {
+            let key = (next.position.x, next.rotation_state());
+            if !seen.contains(&key) {
+                seen.push(key);
+                queue.push_back((next, inputs + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Every place a single key press can move the piece to
+fn moves(piece: &Tetromino, field: &PlayingField) -> Vec<Tetromino> {
+    let mut moves = Vec::new();
+    for dx in [-1, 1] {
+        let mut tap = piece.clone();
+        if !tap.translate(BlockPos::new(dx, 0), field) {
+            let mut das = tap.clone();
+            while !das.translate(BlockPos::new(dx, 0), field) {}
+            moves.push(tap);
+            moves.push(das);
+        }
+    }
+    for rotation in [
+        Rotation::Clockwise,
+        Rotation::CounterClockwise,
+        Rotation::Half,
+    ] {
+        let mut rotated = piece.clone();
+        if !rotated.rotate(rotation, field) {
+            moves.push(rotated);
+        }
+    }
+    moves
+}
+
+/// The columns of the blocks of a piece and their height above its lowest block. Pieces with
+/// the same footprint land in the same place, even when their rotation states differ.
+fn footprint(piece: &Tetromino) -> Vec<(i32, i32)> {
+    let mut blocks = Vec::new();
+    for (x, column) in piece.get_shape().iter().enumerate() {
+        for (y, block) in column.iter().enumerate() {
+            if let Block::Block { .. } = block {
+                blocks.push((piece.position.x + x as i32, y as i32));
+            }
+        }
+    }
+    let bottom = blocks.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    blocks.iter_mut().for_each(|(_, y)| *y -= bottom);
+    blocks.sort();
+    blocks
+}
@@ -0,0 +1,105 @@
+/// How a board reacts to held keys and how fast its pieces fall, configured per player.
+///
+/// All durations are in milliseconds, the board rounds them to whole ticks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Handling {
+    /// Delayed auto shift: how long a direction has to be held before the piece starts repeating
+    pub das: u64,
+    /// Auto repeat rate: the time between repeated shifts, 0 moves the piece to the wall at once
+    pub arr: u64,
+    /// Soft drop factor: how many times faster than gravity soft drop is, `None` drops the piece
+    /// to the floor at once
+    pub sdf: Option<u32>,
+    /// DAS cut delay: how long a charged auto shift waits after rotating, dropping or holding
+    pub dcd: u64,
+    /// The time it takes a piece to fall by one row, may be a fraction of a tick
+    pub gravity: f64,
+    /// How long a piece may lie on the ground before it locks
+    pub lock_delay: u64,
+    /// How many moves on the ground reset the lock delay
+    pub lock_resets: u32,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self {
+            das: 150,
+            arr: 20,
+            sdf: Some(60),
+            dcd: 0,
+            gravity: 1000.0,
+            lock_delay: 500,
+            lock_resets: 10,
+        }
+    }
+}
+
+impl Handling {
+    pub fn das_ticks(&self) -> u64 {
+        crate::ms_to_ticks(self.das)
+    }
+
+    /// The auto repeat rate in ticks, `None` when shifting is instant
+    pub fn arr_ticks(&self) -> Option<u64> {
+        match self.arr {
+            0 => None,
+            x => Some(crate::ms_to_ticks(x).max(1)),
+        }
+    }
+
+    pub fn dcd_ticks(&self) -> u64 {
+        crate::ms_to_ticks(self.dcd)
+    }
+
+    pub fn lock_delay_ticks(&self) -> u64 {
+        crate::ms_to_ticks(self.lock_delay)
+    }
+
+    /// The number of ticks per row, either by gravity or by soft drop. `None` means the piece
+    /// falls to the floor at once.
+    pub fn fall_interval(&self, soft_drop: bool) -> Option<f64> {
+        let gravity = self.gravity * crate::TICKS_PER_SECOND as f64 / 1000.0;
+        match (soft_drop, self.sdf) {
+            (false, _) => Some(gravity),
+            (true, Some(sdf)) => Some(gravity / sdf.max(1) as f64),
+            (true, None) => None,
+        }
+        .filter(|x| *x > 0.0)
+    }
+
+    pub fn to_json(self) -> json::JsonValue {
+        json::object! {
+            das: self.das,
+            arr: self.arr,
+            sdf: match self.sdf {
+                Some(x) => json::JsonValue::from(x),
+                None => json::JsonValue::from("infinite"),
+            },
+            dcd: self.dcd,
+            gravity: self.gravity,
+            lock_delay: self.lock_delay,
+            lock_resets: self.lock_resets,
+        }
+    }
+
+    /// Read the handling written by [`Handling::to_json`], missing values are left at the default
+    pub fn from_json(data: &json::JsonValue) -> Self {
+        let default = Self::default();
+        Self {
+            das: data["das"].as_u64().unwrap_or(default.das),
+            arr: data["arr"].as_u64().unwrap_or(default.arr),
+            sdf: if data["sdf"] == "infinite" {
+                None
+            } else {
+                data["sdf"].as_u32().map_or(default.sdf, Some)
+            },
+            dcd: data["dcd"].as_u64().unwrap_or(default.dcd),
+            gravity: data["gravity"]
+                .as_f64()
+                .filter(|x| *x >= 0.0)
+                .unwrap_or(default.gravity),
+            lock_delay: data["lock_delay"].as_u64().unwrap_or(default.lock_delay),
+            lock_resets: data["lock_resets"].as_u32().unwrap_or(default.lock_resets),
+        }
+    }
+}
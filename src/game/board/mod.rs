@@ -1,22 +1,108 @@
 use super::*;
-use std::cell::RefCell;
 
+mod audio;
 mod effects;
+mod event;
+mod finesse;
+mod handling;
 mod piece_generator;
+mod randomizer;
 mod renderer;
+mod rules;
 mod score;
+mod snapshot;
+mod stats;
 
-use effects::BoardEffects;
+use crate::game::rotation::Rotation;
+pub use audio::BoardAudio;
+pub use effects::BoardEffects;
+pub use event::Event;
+pub use handling::Handling;
 use piece_generator::PieceGenerator;
+pub use randomizer::RandomizerKind;
 pub use renderer::Renderer;
-pub use score::ScoreHandler;
+pub use rules::{AllSpin, Rules};
+pub use score::{ScoreHandler, Spin};
+pub use stats::Stats;
 
-pub type PlayingField = std::collections::VecDeque<Box<[Block; 10]>>;
+/// The rows of the playing field from the bottom up, every row is as wide as the rules say
+pub type PlayingField = std::collections::VecDeque<Box<[Block]>>;
 
+/// An empty playing field of the size given by the rules
+fn empty_field(rules: &Rules) -> PlayingField {
+    (0..rules.rows())
+        .map(|_| vec![Block::Air; rules.width].into_boxed_slice())
+        .collect()
+}
+
+/// Lines which have been cleared, shown until the line clear delay is over
+pub struct Clearing {
+    /// The playing field before the lines have been removed
+    pub blocks: PlayingField,
+    /// The indices of the cleared rows
+    pub rows: Vec<usize>,
+    /// The ticks when the lines have been cleared and when they disappear
+    pub start: u64,
+    pub end: u64,
+}
+
+/// An attack received from another board
+#[derive(Clone, Copy, Debug)]
+pub struct Garbage {
+    pub lines: u32,
+    /// The tick the attack has been received on
+    pub tick: u64,
+}
+
+/// The color of the garbage lines
+const GARBAGE_COLOR: (f32, f32, f32) = (0.3, 0.3, 0.3);
+/// The rows drawn above the visible playing field, where the pieces spawn. The rest of the
+/// playing field is never visible.
+const DRAWN_ROWS: usize = 4;
+/// How many rows a new piece moves up when its spawn position is taken, before the board tops out
+const MAX_SPAWN_BUMP: i32 = 2;
+
+/// The timing state of a held key. All times are tick numbers.
+#[derive(Clone, Copy)]
+enum KeyTiming {
+    /// The key always applies (like soft drop) but doesn't repeat
+    None,
+    Delayed(u64),
+    Repeat(u64),
+    /// The key applies on key press, and it's been applied
+    Single,
+    /// The key has been held before the game started, it repeats from the first tick
+    Charged,
+}
+
+/// A single discrete action which can be performed on the falling piece
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    RotateCW,
+    RotateCCW,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+    Swap,
+}
+
+/// The game logic of a single playing field.
+///
+/// The board knows nothing about windows, audio or graphics. It is advanced by calling
+/// [`Board::tick`] at a fixed rate of [`crate::TICKS_PER_SECOND`] and fed with key presses or
+/// [`Action`]s, and it reports what happened through [`Event`]s.
+///
+/// The board never looks at the clock, so the same seed and the same inputs on the same ticks
+/// always produce the same game.
 pub struct Board {
     blocks: PlayingField,
 
-    keybinds: keys::KeyBinds,
+    held_keys: Vec<(keys::Key, KeyTiming)>,
+    handling: Handling,
+    rotation: Rc<RotationSystem>,
+    rules: Rc<Rules>,
 
     piece_generator: PieceGenerator,
     falling_piece: Tetromino,
@@ -25,131 +111,220 @@ pub struct Board {
 
     /// Whether the piece has already been swapped in this move
     swapped: bool,
+    /// Whether the last successful move of the piece was a rotation, needed for T-spins
+    rotated: bool,
+    /// The keys pressed to move and rotate the current piece, for finesse
+    piece_inputs: u32,
+    /// Whether the current piece has been soft dropped, which finesse doesn't judge
+    soft_dropped: bool,
+    /// Whether the board can top out, in zen the playing field is cleared instead
+    pub top_out: bool,
 
-    last_update_time: std::time::Instant,
-    ground_time: std::time::Instant,
+    /// The number of ticks since the start of the game
+    tick: u64,
+    /// Some while waiting for the next piece to spawn, after the ARE and the line clear delay
+    spawn_tick: Option<u64>,
+    /// The lines cleared last, for the animation
+    clearing: Option<Clearing>,
+    /// The moment the piece has last fallen, in ticks. Gravity can be a fraction of a tick.
+    last_fall: f64,
+    ground_tick: u64,
     moves_on_ground: u32,
     on_ground: bool,
 
-    /// * Some(x) => dead for x amount of time
+    /// * Some(x) => dead since tick x
     /// * None => alive
-    pub death_time: Option<std::time::Instant>,
+    pub death_tick: Option<u64>,
 
-    pub victim: Option<Rc<RefCell<Board>>>,
-    pub lines_received: std::collections::VecDeque<u32>,
+    /// The garbage waiting to rise, oldest first
+    pub lines_received: std::collections::VecDeque<Garbage>,
 
-    effects: BoardEffects,
-    score: ScoreHandler,
+    pub score: ScoreHandler,
 
-    audio: Rc<RefCell<kira::manager::AudioManager>>,
+    /// Used for everything random except the pieces, like the holes in garbage
+    rng: rand::rngs::SmallRng,
 
-    clear_sound: kira::sound::static_sound::StaticSoundData,
-    drop_sound: kira::sound::static_sound::StaticSoundData,
+    events: Vec<Event>,
 }
 
 impl Board {
+    /// Create a new board. Boards created with the same seed get the same pieces.
     pub fn new(
-        keybinds: keys::KeyBinds,
-        rng: rand::rngs::SmallRng,
-        audio: Rc<RefCell<kira::manager::AudioManager>>,
-        roman: &crate::resource::ResourceManager,
+        seed: u64,
+        handling: Handling,
+        rotation: Rc<RotationSystem>,
+        rules: Rc<Rules>,
     ) -> Self {
-        let mut blocks = PlayingField::new();
-        for _ in 0..32 {
-            blocks.push_back(Box::new([Block::Air; 10]));
-        }
-
-        let mut piece_factory = PieceGenerator::new(rng);
-        let falling_piece = Tetromino::new(piece_factory.next_piece());
+        let mut piece_factory = PieceGenerator::new(
+            SmallRng::seed_from_u64(seed),
+            rules.randomizer,
+            rules.preview,
+        );
+        let falling_piece = Tetromino::new(piece_factory.next_piece(), &rotation);
         let ghost_piece = falling_piece.clone();
 
-        let clear_sound = kira::sound::static_sound::StaticSoundData::from_cursor(
-            std::io::Cursor::new((*roman.get_binary("clear.wav")).clone()),
-            kira::sound::static_sound::StaticSoundSettings::default(),
-        )
-        .unwrap();
-
-        let drop_sound = kira::sound::static_sound::StaticSoundData::from_cursor(
-            std::io::Cursor::new((*roman.get_binary("drop.wav")).clone()),
-            kira::sound::static_sound::StaticSoundSettings::default(),
-        )
-        .unwrap();
-
         let mut me = Self {
-            blocks,
+            blocks: empty_field(&rules),
             falling_piece,
             piece_generator: piece_factory,
-            last_update_time: std::time::Instant::now(),
-            ground_time: std::time::Instant::now(),
+            tick: 0,
+            last_fall: 0.0,
+            ground_tick: 0,
             moves_on_ground: 0,
             on_ground: false,
             swapped: false,
+            rotated: false,
+            piece_inputs: 0,
+            soft_dropped: false,
+            top_out: true,
             swap_piece: None,
-            keybinds,
+            held_keys: Vec::new(),
+            handling,
+            rotation,
+            rules,
             ghost_piece,
-            victim: None,
             lines_received: std::collections::VecDeque::new(),
-            death_time: None,
-            effects: BoardEffects::new(0.1, 0.5, 0.1, 0.5),
+            death_tick: None,
+            spawn_tick: None,
+            clearing: None,
             score: ScoreHandler::new(),
-            audio,
-            clear_sound,
-            drop_sound,
+            rng: SmallRng::seed_from_u64(!seed),
+            events: Vec::new(),
         };
+        me.falling_piece = me.new_piece(me.falling_piece.shape);
         me.update_ghost();
         me
     }
 
-    /// The update function should be run every frame. The board calculates its own timings
-    pub fn update(
-        &mut self,
-        keys: &mut std::collections::HashMap<glutin::event::VirtualKeyCode, KeyTiming>,
-    ) {
-        self.effects.update();
+    /// Take all the events which happened since the last call
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, Event> {
+        self.events.drain(..)
+    }
+
+    /// Start holding down a key, it will be handled on the next update
+    pub fn press(&mut self, key: keys::Key) {
+        if !self.holding(key) {
+            self.held_keys.push((key, KeyTiming::None));
+            // choosing a target doesn't move the piece
+            if key != keys::Key::Target {
+                self.events.push(Event::KeyPressed);
+            }
+        }
+    }
+
+    /// Let the keys moving the piece which are held before the first tick repeat at once, like
+    /// DAS charged during the countdown
+    pub fn charge_das(&mut self) {
+        for (key, timing) in &mut self.held_keys {
+            if matches!(key, keys::Key::Left | keys::Key::Right)
+                && matches!(timing, KeyTiming::None)
+            {
+                *timing = KeyTiming::Charged;
+            }
+        }
+    }
+
+    /// Whether the key is being held down
+    pub fn holding(&self, key: keys::Key) -> bool {
+        self.held_keys.iter().any(|(k, _)| *k == key)
+    }
+
+    /// Stop holding down a key
+    pub fn release(&mut self, key: keys::Key) {
+        self.held_keys.retain(|(k, _)| *k != key);
+    }
+
+    /// Queue `n` lines of garbage which will be inserted when the next piece lands
+    pub fn receive_garbage(&mut self, n: u32) {
+        if n > 0 {
+            self.lines_received.push_back(Garbage {
+                lines: n,
+                tick: self.tick,
+            });
+        }
+    }
+
+    /// Change how long it takes a piece to fall by one row, in milliseconds
+    pub fn set_gravity(&mut self, gravity: f64) {
+        self.handling.gravity = gravity;
+    }
+
+    /// The number of rows from the floor up to the highest block on the board
+    pub fn stack_height(&self) -> u32 {
+        self.blocks
+            .iter()
+            .rposition(|row| row.iter().any(|x| matches!(x, Block::Block { .. })))
+            .map_or(0, |x| x as u32 + 1)
+    }
+
+    /// Advance the game by one tick
+    pub fn tick(&mut self) {
         // exit immediately if we are dead
-        if let Some(_) = self.death_time {
+        if self.death_tick.is_some() {
             return;
         }
+        self.tick += 1;
 
-        let now = std::time::Instant::now();
+        if self.spawn_tick.is_some_and(|x| self.tick >= x) {
+            self.spawn_tick = None;
+            self.spawn();
+            if self.death_tick.is_some() {
+                return;
+            }
+        }
 
         // handle input and set soft drop
         let mut soft_drop = false;
-        self.handle_input(keys, now, &mut soft_drop);
-
-        // run gravity if timeout expired
-        if now.duration_since(self.last_update_time)
-            >= std::time::Duration::from_millis(if soft_drop {
-                self.effects.velocity.y -= 0.01;
-                20
-            } else {
-                1000
-            })
-        {
-            self.soft_drop();
-            self.last_update_time = now;
+        self.handle_input(&mut soft_drop);
+
+        // there is no piece yet, or the board has topped out
+        if self.spawn_tick.is_some() || self.death_tick.is_some() {
+            return;
         }
 
+        self.gravity(soft_drop);
+
         // land the piece if timeout expired and on ground
-        if (now.duration_since(self.ground_time) > std::time::Duration::from_millis(500)
-            || self.moves_on_ground > 10)
+        if (self.tick - self.ground_tick > self.handling.lock_delay_ticks()
+            || self.moves_on_ground > self.handling.lock_resets)
             && self.on_ground
         {
             self.land_piece();
         }
     }
 
-    fn handle_input(
-        &mut self,
-        keys: &mut std::collections::HashMap<glutin::event::VirtualKeyCode, KeyTiming>,
-        now: std::time::Instant,
-        soft_drop: &mut bool,
-    ) {
-        for (key, timing) in keys {
-            let key = match self.keybinds.decode(*key) {
-                Some(x) => x,
-                None => continue,
-            };
+    /// Let the piece fall by all the rows it should have fallen since the last time
+    fn gravity(&mut self, soft_drop: bool) {
+        let now = self.tick as f64;
+        let rows = match self.handling.fall_interval(soft_drop) {
+            Some(interval) => {
+                // don't let the time spent at a slower gravity speed up the current one
+                self.last_fall = self.last_fall.max(now - interval.max(1.0));
+                let rows = ((now - self.last_fall) / interval).floor();
+                if rows < 1.0 {
+                    return;
+                }
+                self.last_fall += rows * interval;
+                // a piece can't fall further than the whole playing field
+                (rows as u32).min(self.blocks.len() as u32)
+            }
+            None => {
+                self.last_fall = now;
+                self.blocks.len() as u32
+            }
+        };
+        self.fall(rows, soft_drop);
+        if self.on_ground {
+            self.last_fall = now;
+        }
+    }
+
+    fn handle_input(&mut self, soft_drop: &mut bool) {
+        let now = self.tick;
+        let das = self.handling.das_ticks();
+        let arr = self.handling.arr_ticks();
+        let mut actions = Vec::new();
+        for (key, timing) in &mut self.held_keys {
             let mut run = false;
             *timing = match timing {
                 KeyTiming::None => match key {
@@ -158,16 +333,22 @@ impl Board {
                         KeyTiming::None
                     }
                     keys::Key::Left | keys::Key::Right => {
+                        self.piece_inputs += 1;
                         run = true;
                         KeyTiming::Delayed(now)
                     }
+                    keys::Key::RotateCW | keys::Key::RotateCCW | keys::Key::Rotate180 => {
+                        self.piece_inputs += 1;
+                        run = true;
+                        KeyTiming::Single
+                    }
                     _ => {
                         run = true;
                         KeyTiming::Single
                     }
                 },
                 KeyTiming::Delayed(t) => {
-                    if now.duration_since(*t) >= std::time::Duration::from_millis(150) {
+                    if now - *t >= das {
                         run = true;
                         KeyTiming::Repeat(now)
                     } else {
@@ -175,7 +356,8 @@ impl Board {
                     }
                 }
                 KeyTiming::Repeat(t) => {
-                    if now.duration_since(*t) >= std::time::Duration::from_millis(20) {
+                    // without a repeat rate the piece is pushed against the wall on every tick
+                    if arr.is_none_or(|arr| now - *t >= arr) {
                         run = true;
                         KeyTiming::Repeat(now)
                     } else {
@@ -183,107 +365,168 @@ impl Board {
                     }
                 }
                 KeyTiming::Single => KeyTiming::Single,
+                KeyTiming::Charged => {
+                    self.piece_inputs += 1;
+                    run = true;
+                    KeyTiming::Repeat(now)
+                }
             };
 
             if run {
-                if self.on_ground {
-                    self.moves_on_ground += 1;
-                }
-                match key {
-                    keys::Key::Left => self.move_left(),
-                    keys::Key::Right => self.move_right(),
-                    keys::Key::RotateCW => self.rotate_cw(),
-                    keys::Key::RotateCCW => self.rotate_ccw(),
-                    keys::Key::HardDrop => self.hard_drop(),
-                    keys::Key::Swap => self.swap(),
-                    _ => (),
-                }
-                self.ground_time = std::time::Instant::now();
+                // keys like targeting are held here too, but they don't do anything to the board
+                let repeats = match (timing, arr) {
+                    (KeyTiming::Repeat(_), None) => self.rules.width,
+                    _ => 1,
+                };
+                actions.extend(
+                    match key {
+                        keys::Key::Left => Some(Action::MoveLeft),
+                        keys::Key::Right => Some(Action::MoveRight),
+                        keys::Key::RotateCW => Some(Action::RotateCW),
+                        keys::Key::RotateCCW => Some(Action::RotateCCW),
+                        keys::Key::Rotate180 => Some(Action::Rotate180),
+                        keys::Key::SoftDrop => Some(Action::SoftDrop),
+                        keys::Key::HardDrop => Some(Action::HardDrop),
+                        keys::Key::Swap => Some(Action::Swap),
+                        keys::Key::Target | keys::Key::Restart | keys::Key::Pause => None,
+                    }
+                    .map(|x| (x, repeats)),
+                );
+            }
+        }
+
+        // without a piece the keys only charge DAS, or apply once it spawns
+        if self.spawn_tick.is_some() {
+            return;
+        }
+
+        for (action, repeats) in actions {
+            if self.on_ground {
+                self.moves_on_ground += 1;
+            }
+            for _ in 0..repeats {
+                self.apply(action);
+            }
+            self.ground_tick = now;
+            if matches!(
+                action,
+                Action::RotateCW
+                    | Action::RotateCCW
+                    | Action::Rotate180
+                    | Action::HardDrop
+                    | Action::Swap
+            ) {
+                self.cut_das();
             }
         }
     }
 
-    fn rotate_cw(&mut self) {
-        self.falling_piece.rotate_cw(&self.blocks);
-        self.test_ground();
-        self.update_ghost();
+    /// Hold back the auto shift which is already charged for the DAS cut delay
+    fn cut_das(&mut self) {
+        let dcd = self.handling.dcd_ticks();
+        if dcd == 0 {
+            return;
+        }
+        // a delayed key starts repeating DAS ticks after it's been pressed
+        let start = (self.tick + dcd).saturating_sub(self.handling.das_ticks());
+        for (_, timing) in &mut self.held_keys {
+            if let KeyTiming::Repeat(_) = timing {
+                *timing = KeyTiming::Delayed(start);
+            }
+        }
     }
 
-    fn rotate_ccw(&mut self) {
-        self.falling_piece.rotate_ccw(&self.blocks);
-        self.test_ground();
-        self.update_ghost();
+    /// Perform a single action on the falling piece, regardless of the keys being held
+    pub fn apply(&mut self, action: Action) {
+        // the piece may have been locked by a previous action, waiting for the next one
+        if self.death_tick.is_some() || self.spawn_tick.is_some() {
+            return;
+        }
+        match action {
+            Action::MoveLeft => self.shift(-1),
+            Action::MoveRight => self.shift(1),
+            Action::RotateCW => self.rotate_cw(),
+            Action::RotateCCW => self.rotate_ccw(),
+            Action::Rotate180 => self.rotate_180(),
+            Action::SoftDrop => self.fall(1, true),
+            Action::HardDrop => self.hard_drop(),
+            Action::Swap => self.swap(),
+        }
     }
 
-    fn soft_drop(&mut self) {
-        self.falling_piece
-            .translate(BlockPos::new(0, -1), &self.blocks);
+    fn rotate_cw(&mut self) {
+        if !self.falling_piece.rotate_cw(&self.blocks) {
+            self.rotated = true;
+        }
         self.test_ground();
+        self.update_ghost();
     }
 
-    fn move_left(&mut self) {
-        if self
-            .falling_piece
-            .translate(BlockPos::new(-1, 0), &self.blocks)
-        {
-            self.effects.velocity.x -= 0.03;
+    fn rotate_ccw(&mut self) {
+        if !self.falling_piece.rotate_ccw(&self.blocks) {
+            self.rotated = true;
         }
         self.test_ground();
         self.update_ghost();
     }
 
-    fn move_right(&mut self) {
-        if self
-            .falling_piece
-            .translate(BlockPos::new(1, 0), &self.blocks)
-        {
-            self.effects.velocity.x += 0.03;
+    fn rotate_180(&mut self) {
+        if !self.falling_piece.rotate_180(&self.blocks) {
+            self.rotated = true;
         }
         self.test_ground();
         self.update_ghost();
     }
 
-    fn hard_drop(&mut self) {
-        self.effects.velocity.y -= 0.15;
-        loop {
-            self.hard_drop_fly_particles();
+    /// Move the piece down by up to `rows` blocks, either by gravity or by soft drop
+    fn fall(&mut self, rows: u32, soft_drop: bool) {
+        let mut fallen = 0;
+        for _ in 0..rows {
             if self
                 .falling_piece
                 .translate(BlockPos::new(0, -1), &self.blocks)
             {
                 break;
             }
+            fallen += 1;
         }
-        self.audio
-            .borrow_mut()
-            .play(self.drop_sound.clone())
-            .unwrap();
-        self.land_piece();
+        if fallen > 0 {
+            self.rotated = false;
+        }
+        if fallen > 0 && soft_drop {
+            self.soft_dropped = true;
+            self.score.drop(fallen, false);
+            self.events.push(Event::SoftDropped);
+        }
+        self.test_ground();
     }
 
-    fn hard_drop_fly_particles(&mut self) {
-        let shape = self.falling_piece.get_shape();
-        for x in 0..4 {
-            for y in 0..4 {
-                if let Block::Block { .. } = shape[x][y] {
-                    if rand::random::<u32>() % 10 == 0 {
-                        self.effects.particles.push(effects::Particle::new(
-                            glam::Vec2::new(
-                                (self.falling_piece.position.x + x as i32) as f32
-                                    + rand::random::<f32>(),
-                                (self.falling_piece.position.y + y as i32) as f32
-                                    + rand::random::<f32>(),
-                            ),
-                            glam::Vec2::new(0.0, 0.1 * rand::random::<f32>()),
-                            glam::Vec2::new(0.0, 0.01),
-                            0.2 * rand::random::<f32>(),
-                            0.005,
-                            effects::ParticleModel::random_color(),
-                        ));
-                    }
-                }
-            }
+    fn shift(&mut self, dx: i32) {
+        if !self
+            .falling_piece
+            .translate(BlockPos::new(dx, 0), &self.blocks)
+        {
+            self.rotated = false;
+            self.events.push(Event::Shifted(dx));
         }
+        self.test_ground();
+        self.update_ghost();
+    }
+
+    fn hard_drop(&mut self) {
+        let from = self.falling_piece.clone();
+        while !self
+            .falling_piece
+            .translate(BlockPos::new(0, -1), &self.blocks)
+        {
+            self.rotated = false;
+            self.score.drop(1, true);
+        }
+        self.events.push(Event::HardDropped {
+            from,
+            to: self.falling_piece.clone(),
+        });
+        self.land_piece();
     }
 
     fn swap(&mut self) {
@@ -293,145 +536,334 @@ impl Board {
         let new_piece = self
             .swap_piece
             .take()
-            .or_else(|| Some(self.piece_generator.next_piece()))
-            .unwrap();
+            .unwrap_or_else(|| self.piece_generator.next_piece());
         self.swap_piece = Some(self.falling_piece.shape);
-        self.falling_piece = Tetromino::new(new_piece);
+        self.falling_piece = self.new_piece(new_piece);
+        self.bump();
         self.swapped = true;
+        self.rotated = false;
+        self.piece_inputs = 0;
+        self.soft_dropped = false;
         self.update_ghost();
     }
 
+    /// Decide whether the falling piece is a spin. T pieces are always checked, the others only
+    /// with all spins, when they can't move left, right or up after a rotation.
+    fn spin(&self) -> Spin {
+        let piece = &self.falling_piece;
+        match (piece.shape, self.rules.all_spin) {
+            (tetromino::Shape::T, _) => self.t_spin(),
+            (tetromino::Shape::O, _) | (_, AllSpin::Off) => Spin::None,
+            (_, all_spin) => {
+                let immobile = [(-1, 0), (1, 0), (0, 1)]
+                    .iter()
+                    .all(|(x, y)| piece.clone().translate(BlockPos::new(*x, *y), &self.blocks));
+                match (self.rotated && immobile, all_spin) {
+                    (false, _) => Spin::None,
+                    (true, AllSpin::Mini) => Spin::Mini,
+                    (true, _) => Spin::Full,
+                }
+            }
+        }
+    }
+
+    /// Decide whether the falling piece is a T-spin by the three corner rule: the last move
+    /// has to be a rotation and three of the four corners around the center of the T have to be
+    /// filled. It is a mini unless both corners in front of the T are filled, or the rotation
+    /// used the kick which moves the piece by one column and two rows (the TST and fin kicks).
+    fn t_spin(&self) -> Spin {
+        let piece = &self.falling_piece;
+        if piece.shape != tetromino::Shape::T || !self.rotated {
+            return Spin::None;
+        }
+        let blocks = self.rotation.blocks(piece.shape, piece.rotation_state());
+        let is_block = |x: BlockPos| blocks.iter().any(|b| b.x == x.x && b.y == x.y);
+
+        // the center of the T touches the three other blocks
+        let sides = [
+            BlockPos::new(1, 0),
+            BlockPos::new(-1, 0),
+            BlockPos::new(0, 1),
+            BlockPos::new(0, -1),
+        ];
+        let center = match blocks
+            .iter()
+            .find(|x| sides.iter().filter(|side| is_block(**x + **side)).count() == 3)
+        {
+            Some(x) => *x,
+            None => return Spin::None,
+        };
+        // and the T points away from its flat side
+        let back = match sides.iter().find(|side| !is_block(center + **side)) {
+            Some(x) => *x,
+            None => return Spin::None,
+        };
+
+        let filled = |corner: BlockPos| {
+            let x = piece.position.x + corner.x;
+            let y = piece.position.y + corner.y;
+            x < 0
+                || y < 0
+                || matches!(
+                    self.blocks.get(y as usize).map(|row| row.get(x as usize)),
+                    Some(None | Some(Block::Block { .. }))
+                )
+        };
+        let mut corners = 0;
+        let mut front = 0;
+        for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            if filled(center + BlockPos::new(dx, dy)) {
+                corners += 1;
+                // the corners in front have the opposite direction than the flat side
+                if dx * back.x + dy * back.y < 0 {
+                    front += 1;
+                }
+            }
+        }
+
+        let kick = piece.kick();
+        if corners < 3 {
+            Spin::None
+        } else if front == 2 || (kick.x.abs() == 1 && kick.y.abs() == 2) {
+            Spin::Full
+        } else {
+            Spin::Mini
+        }
+    }
+
     fn land_piece(&mut self) {
-        // test whether there is a block above out piece
         // this is used for score calculation and must be tested BEFORE the block has been landed
-        let covered = self.test_translation(BlockPos::new(0, 1));
+        let spin = self.spin();
 
-        let mut top = 0;
+        let mut bottom = i32::MAX;
 
         // convert the piece into blocks
         let piece = self.falling_piece.get_shape();
-        for y in 0..4 {
-            for x in 0..4 {
-                if let Block::Block { .. } = piece[x][y] {
+        for (x, column) in piece.iter().enumerate() {
+            for (y, block) in column.iter().enumerate() {
+                if let Block::Block { .. } = block {
                     let board_x = self.falling_piece.position.x + x as i32;
                     let board_y = self.falling_piece.position.y + y as i32;
-                    if board_y - 19 > top {
-                        top = board_y;
-                    }
-                    self.blocks[board_y as usize][board_x as usize] = piece[x as usize][y as usize];
+                    bottom = bottom.min(board_y);
+                    self.blocks[board_y as usize][board_x as usize] = *block;
                 }
             }
         }
 
-        self.land_aftermath(self.falling_piece.position.y, top, covered);
-        self.land_particles();
+        self.events
+            .push(Event::PieceLocked(self.falling_piece.clone()));
+        if !self.soft_dropped
+            && finesse::optimal_inputs(
+                &self.falling_piece,
+                self.new_piece(self.falling_piece.shape),
+                &self.rules,
+            )
+            .is_some_and(|x| self.piece_inputs > x)
+        {
+            self.events.push(Event::FinesseFault);
+        }
+        // lock out: the piece is entirely above the visible playing field
+        let lock_out = bottom >= self.rules.height as i32;
+        let cleared = self.land_aftermath(self.falling_piece.position.y, lock_out, spin);
+        if self.death_tick.is_some() {
+            return;
+        }
+
+        match self.rules.spawn_delay_ticks(cleared > 0) {
+            0 => self.spawn(),
+            x => self.spawn_tick = Some(self.tick + x),
+        }
+    }
 
-        // draw a new piece and reset everything
-        self.falling_piece = Tetromino::new(self.piece_generator.next_piece());
+    /// Draw a new piece and reset everything. Holding the hold key or a rotation key when the
+    /// piece spawns swaps or rotates it at once (IHS and IRS).
+    fn spawn(&mut self) {
+        let shape = self.piece_generator.next_piece();
+        self.falling_piece = self.new_piece(shape);
         self.on_ground = false;
         self.moves_on_ground = 0;
         self.swapped = false;
+        self.rotated = false;
+        self.piece_inputs = 0;
+        self.soft_dropped = false;
+
+        if self.holding(keys::Key::Swap) {
+            self.swap();
+        } else {
+            self.bump();
+        }
+        if self.death_tick.is_some() {
+            return;
+        }
+        // the rotation pressed last wins
+        let rotation = self.held_keys.iter_mut().rev().find_map(|(key, timing)| {
+            let rotation = match key {
+                keys::Key::RotateCW => Rotation::Clockwise,
+                keys::Key::RotateCCW => Rotation::CounterClockwise,
+                keys::Key::Rotate180 => Rotation::Half,
+                _ => return None,
+            };
+            // it has been applied to this piece now
+            *timing = KeyTiming::Single;
+            Some(rotation)
+        });
+        if let Some(x) = rotation {
+            if !self.falling_piece.rotate(x, &self.blocks) {
+                self.rotated = true;
+                self.piece_inputs += 1;
+            }
+            self.test_ground();
+        }
         self.update_ghost();
     }
 
-    fn land_aftermath(&mut self, mut piece_position: i32, mut piece_top: i32, covered: bool) {
-        // add a little bump for landing the piece
-        self.effects.velocity.y -= 0.075;
+    /// Create a piece at the spawn position of the rotation system, which is made for a 10 by 20
+    /// playing field, moved to the middle and the top of this one
+    fn new_piece(&self, shape: tetromino::Shape) -> Tetromino {
+        let offset = BlockPos::new(
+            (self.rules.width as i32 - 10) / 2,
+            self.rules.height as i32 - 20,
+        );
+        Tetromino::placed(
+            shape,
+            self.rotation.spawn(shape) + offset,
+            0,
+            &self.rotation,
+        )
+    }
 
-        let mut lines_cleared = 0;
+    /// Move a new piece up when its spawn position is taken, like tetr.io, and top out when it
+    /// still doesn't fit (block out)
+    fn bump(&mut self) {
+        for _ in 0..MAX_SPAWN_BUMP {
+            if !self.falling_piece.obstructed(&self.blocks) {
+                return;
+            }
+            self.falling_piece.position.y += 1;
+        }
+        if self.falling_piece.obstructed(&self.blocks) {
+            self.top_out_now();
+        }
+    }
+
+    /// End the game, or clear the playing field when the board can't top out
+    fn top_out_now(&mut self) {
+        if self.top_out {
+            self.death_tick = Some(self.tick);
+            self.events.push(Event::ToppedOut);
+        } else {
+            self.blocks.iter_mut().for_each(|x| x.fill(Block::Air));
+            self.lines_received.clear();
+        }
+    }
+
+    /// Clear the lines, send and receive garbage, and return the number of lines cleared
+    fn land_aftermath(&mut self, piece_position: i32, lock_out: bool, spin: Spin) -> u32 {
+        let delay = self.rules.line_clear_delay_ticks();
+        let before = (delay > 0).then(|| self.blocks.clone());
+        let mut cleared_rows = Vec::new();
+        let mut garbage = 0;
 
         // scan through lines to find filled lines
         // scan from the top so that we don't skip any lines
-        'l: for y in (piece_position.clamp(0, 32)..(piece_position + 4).clamp(0, 32)).rev() {
-            for x in 0..10 {
-                if let Block::Air = self.blocks[y as usize][x] {
-                    continue 'l;
+        let rows = self.blocks.len() as i32;
+        for y in (piece_position.clamp(0, rows)..(piece_position + 4).clamp(0, rows)).rev() {
+            if self.blocks[y as usize]
+                .iter()
+                .all(|x| matches!(x, Block::Block { .. }))
+            {
+                cleared_rows.push(y as usize);
+                if self.blocks[y as usize]
+                    .iter()
+                    .any(|x| matches!(x, Block::Block { color } if *color == GARBAGE_COLOR))
+                {
+                    garbage += 1;
                 }
+                self.remove_line(y as _);
             }
-            lines_cleared += 1;
-            self.remove_line(y as _);
-            piece_position -= 1;
-            piece_top -= 1;
         }
 
+        let lines_cleared = cleared_rows.len() as u32;
         if lines_cleared > 0 {
-            self.audio
-                .borrow_mut()
-                .play(self.clear_sound.clone().with_modified_settings(|_| {
-                    kira::sound::static_sound::StaticSoundSettings::new()
-                        .playback_rate(kira::PlaybackRate::Semitones(self.score.combo as _))
-                }))
-                .unwrap();
+            self.clearing = before.map(|blocks| Clearing {
+                blocks,
+                rows: cleared_rows.clone(),
+                start: self.tick,
+                end: self.tick + delay,
+            });
         }
+        let perfect_clear = self.rules.perfect_clear
+            && self
+                .blocks
+                .iter()
+                .all(|row| row.iter().all(|x| matches!(x, Block::Air)));
 
-        let (mut lines_to_send, message) =
-            self.score
-                .analyze(lines_cleared, self.falling_piece.shape, covered);
+        let (mut lines_to_send, message) = self.score.analyze(
+            lines_cleared,
+            self.falling_piece.shape,
+            spin,
+            perfect_clear,
+            &self.rules,
+        );
 
-        if let Some(x) = message {
-            self.effects.info = Some(effects::InfoText {
-                text: x,
-                time: std::time::Instant::now(),
+        if lines_cleared > 0 {
+            self.events.push(Event::LinesCleared {
+                rows: cleared_rows,
+                combo: self.score.combo,
+                garbage,
+                attack: lines_to_send,
             });
         }
 
-        self.effects.velocity.y -= 0.1 * lines_to_send as f32;
+        if let Some(x) = message {
+            self.events.push(Event::Announced(x));
+        }
 
         // If there are pending lines, block the amount of lines we would normally send
-        if let Some(mut x) = self.lines_received.pop_front() {
-            if x > lines_to_send {
-                x -= lines_to_send;
-                lines_to_send = 0;
-                self.insert_cheese(x as _);
-                piece_top += x as i32;
-                self.effects.velocity.y += 0.1 * x as f32;
-            } else {
-                lines_to_send -= x;
+        if self.rules.cancel {
+            while let Some(x) = self.lines_received.front_mut() {
+                if lines_to_send == 0 {
+                    break;
+                }
+                let cancelled = x.lines.min(lines_to_send);
+                x.lines -= cancelled;
+                lines_to_send -= cancelled;
+                if x.lines == 0 {
+                    self.lines_received.pop_front();
+                }
             }
         }
 
-        // Die if we have reached the top
-        if piece_top >= 20 {
-            self.death_time = Some(std::time::Instant::now());
-            return;
+        // and let the rest rise, once it has waited long enough
+        // garbage out: the garbage pushes blocks out of the top of the playing field
+        let mut garbage_out = false;
+        if lines_cleared == 0 || self.rules.garbage_on_clear {
+            let (risen, out) = self.raise_garbage();
+            garbage_out = out;
+            if risen > 0 {
+                self.events.push(Event::GarbageReceived(risen));
+            }
         }
 
-        // Send lines only if we didn't die
-        if let Some(v) = &mut self.victim {
-            if lines_to_send > 0 {
-                v.borrow_mut().lines_received.push_back(lines_to_send);
+        if lock_out || garbage_out {
+            self.top_out_now();
+            if self.death_tick.is_some() {
+                return lines_cleared;
             }
         }
-    }
 
-    fn land_particles(&mut self) {
-        let shape = self.falling_piece.get_shape();
-        for x in 0..4 {
-            for y in 0..4 {
-                if let Block::Block { .. } = shape[x][y] {
-                    self.effects.particles.push(effects::Particle::new(
-                        glam::Vec2::new(
-                            (self.falling_piece.position.x + x as i32) as f32
-                                + rand::random::<f32>(),
-                            (self.falling_piece.position.y + y as i32) as f32
-                                + rand::random::<f32>(),
-                        ),
-                        glam::Vec2::new(0.0, 0.0),
-                        glam::Vec2::new(0.0, 0.001),
-                        0.3 * rand::random::<f32>(),
-                        0.005,
-                        effects::ParticleModel::Star,
-                    ));
-                }
-            }
+        // Send lines only if we didn't die
+        if lines_to_send > 0 {
+            self.events.push(Event::GarbageSent(lines_to_send));
         }
+        lines_cleared
     }
 
     fn test_ground(&mut self) {
         let old_ground = self.on_ground;
         self.on_ground = self.test_translation(BlockPos::new(0, -1));
         if self.on_ground && !old_ground {
-            self.ground_time = std::time::Instant::now();
+            self.ground_tick = self.tick;
         }
     }
 
@@ -461,48 +893,279 @@ impl Board {
 
     /// Remove line n
     fn remove_line(&mut self, n: usize) {
-        self.line_clear_particles(n);
-        // The row should be full if the loop passed through everything
-        let mut boks = Box::new([Block::Air; 10]);
-        for i in n..30 {
-            std::mem::swap(&mut self.blocks[i + 1], &mut boks);
-            std::mem::swap(&mut self.blocks[i], &mut boks);
-            std::mem::swap(&mut self.blocks[i + 1], &mut boks);
-        }
-        std::mem::swap(&mut self.blocks[31], &mut boks);
-        std::mem::swap(&mut self.blocks[30], &mut boks);
-    }
-
-    fn line_clear_particles(&mut self, y: usize) {
-        for x in 0..10 {
-            self.effects.particles.push(effects::Particle::new(
-                glam::Vec2::new(
-                    x as f32 + rand::random::<f32>(),
-                    y as f32 + rand::random::<f32>(),
-                ),
-                glam::Vec2::new((rand::random::<f32>() - 0.5) * 0.1, 0.1),
-                glam::Vec2::new(0.0, -0.01),
-                0.4 * rand::random::<f32>(),
-                0.005,
-                effects::ParticleModel::Star,
-            ));
-        }
-    }
-
-    /// Insert n lines of "cheese" at the bottom of the game
-    fn insert_cheese(&mut self, n: usize) {
-        let spot = rand::random::<usize>() % 10;
-        for _ in 0..n {
+        // the rows above move down and an empty one comes in at the top
+        self.blocks.remove(n);
+        self.blocks
+            .push_back(vec![Block::Air; self.rules.width].into_boxed_slice());
+    }
+
+    /// Insert the garbage which has waited for long enough, up to the cap of the rules, and return
+    /// the number of lines inserted and whether blocks have been pushed out of the playing field
+    fn raise_garbage(&mut self) -> (u32, bool) {
+        let mut risen = 0;
+        let mut out = false;
+        let cap = self.rules.garbage_cap.unwrap_or(u32::MAX);
+        while let Some(x) = self.lines_received.front_mut() {
+            if risen >= cap || self.tick < x.tick + self.rules.garbage_delay_ticks() {
+                break;
+            }
+            let lines = x.lines.min(cap - risen);
+            x.lines -= lines;
+            if x.lines == 0 {
+                self.lines_received.pop_front();
+            }
+            out |= self.insert_cheese(lines as _);
+            risen += lines;
+        }
+        (risen, out)
+    }
+
+    /// Insert n lines of "cheese" at the bottom of the game, and return whether blocks have been
+    /// pushed out of the top
+    fn insert_cheese(&mut self, n: usize) -> bool {
+        let mut out = false;
+        let width = self.rules.width;
+        let mut spot = self.rng.gen_range(0..width);
+        for i in 0..n {
+            // messy garbage moves the hole within an attack
+            if i > 0 && self.rng.gen_bool(self.rules.messiness) {
+                spot = (spot + self.rng.gen_range(1..width)) % width;
+            }
             self.blocks.push_front({
-                let mut line = Box::new(
-                    [Block::Block {
-                        color: (0.3, 0.3, 0.3),
-                    }; 10],
-                );
+                let mut line = vec![
+                    Block::Block {
+                        color: GARBAGE_COLOR,
+                    };
+                    width
+                ]
+                .into_boxed_slice();
                 line[spot] = Block::Air;
                 line
             });
-            self.blocks.pop_back();
+            out |= self
+                .blocks
+                .pop_back()
+                .is_some_and(|x| x.iter().any(|x| matches!(x, Block::Block { .. })));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetromino::Shape;
+
+    fn board(rules: Rules) -> Board {
+        Board::new(
+            1,
+            Handling::default(),
+            Rc::new(RotationSystem::srs_plus()),
+            Rc::new(rules),
+        )
+    }
+
+    /// Fill the row with garbage, except for the given columns
+    fn fill(board: &mut Board, y: usize, holes: &[usize]) {
+        for (x, block) in board.blocks[y].iter_mut().enumerate() {
+            if !holes.contains(&x) {
+                *block = Block::Block {
+                    color: GARBAGE_COLOR,
+                };
+            }
+        }
+    }
+
+    /// Make the given piece fall next, in place of the current one
+    fn set_piece(board: &mut Board, piece: Tetromino) {
+        board.falling_piece = piece;
+        board.update_ghost();
+    }
+
+    /// A slot for a T-spin double under an overhang on the left, the T fits in it pointing down
+    /// with its center at (1, 1)
+    fn t_slot(board: &mut Board) {
+        fill(board, 0, &[1]);
+        fill(board, 1, &[0, 1, 2]);
+        board.blocks[2][0] = Block::Block {
+            color: GARBAGE_COLOR,
+        };
+    }
+
+    fn events(board: &mut Board) -> Vec<Event> {
+        board.drain_events().collect()
+    }
+
+    #[test]
+    fn hard_drop_clears_a_line() {
+        let mut board = board(Rules::tetrio());
+        let piece = Tetromino::new(Shape::I, &board.rotation);
+        let columns = (0..4)
+            .filter(|x| {
+                piece.get_shape()[*x]
+                    .iter()
+                    .any(|b| matches!(b, Block::Block { .. }))
+            })
+            .map(|x| (piece.position.x + x as i32) as usize)
+            .collect::<Vec<usize>>();
+        fill(&mut board, 0, &columns);
+        set_piece(&mut board, piece);
+
+        board.apply(Action::HardDrop);
+        let events = events(&mut board);
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::LinesCleared { rows, .. } if *rows == [0])));
+        assert_eq!(board.stack_height(), 0);
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let mut board = board(Rules::tetrio());
+        t_slot(&mut board);
+        let piece = Tetromino::placed(Shape::T, BlockPos::new(0, -1), 1, &board.rotation);
+        set_piece(&mut board, piece);
+
+        board.apply(Action::RotateCW);
+        board.apply(Action::HardDrop);
+        let events = events(&mut board);
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::LinesCleared { rows, .. } if rows.len() == 2)));
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::Announced(text) if text.contains("T-SPIN DOUBLE"))));
+        assert!(events.iter().any(|x| matches!(x, Event::GarbageSent(4))));
+    }
+
+    #[test]
+    fn t_spin_mini_with_one_corner_in_front() {
+        let mut board = board(Rules::tetrio());
+        // the T turns up into the row with both corners below it filled, but only one above
+        fill(&mut board, 0, &[1]);
+        fill(&mut board, 1, &[0, 1, 2]);
+        board.blocks[2][0] = Block::Block {
+            color: GARBAGE_COLOR,
+        };
+        let piece = Tetromino::placed(Shape::T, BlockPos::new(0, -1), 1, &board.rotation);
+        set_piece(&mut board, piece);
+
+        board.apply(Action::RotateCCW);
+        board.apply(Action::HardDrop);
+        let events = events(&mut board);
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::LinesCleared { rows, .. } if *rows == [1])));
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::Announced(text) if text == "T-SPIN MINI")));
+    }
+
+    #[test]
+    fn no_t_spin_without_rotating() {
+        let mut board = board(Rules::tetrio());
+        t_slot(&mut board);
+        let piece = Tetromino::placed(Shape::T, BlockPos::new(0, -1), 2, &board.rotation);
+        set_piece(&mut board, piece);
+
+        board.apply(Action::HardDrop);
+        let events = events(&mut board);
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::LinesCleared { rows, .. } if rows.len() == 2)));
+        assert!(!events
+            .iter()
+            .any(|x| matches!(x, Event::Announced(text) if text.contains("T-SPIN"))));
+        assert!(events.iter().any(|x| matches!(x, Event::GarbageSent(1))));
+    }
+
+    #[test]
+    fn garbage_rises_after_the_delay() {
+        let mut board = board(Rules::tetrio());
+        board.receive_garbage(3);
+        for _ in 0..board.rules.garbage_delay_ticks() {
+            board.tick();
         }
+
+        board.apply(Action::HardDrop);
+        let events = events(&mut board);
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, Event::GarbageReceived(3))));
+        assert!(board.lines_received.is_empty());
+        assert_eq!(board.stack_height(), 5);
+    }
+
+    #[test]
+    fn tops_out_when_locking_above_the_field() {
+        let mut board = board(Rules::tetrio());
+        for y in 0..board.rules.height {
+            fill(&mut board, y, &[0]);
+        }
+
+        board.apply(Action::HardDrop);
+        assert!(events(&mut board)
+            .iter()
+            .any(|x| matches!(x, Event::ToppedOut)));
+        assert!(board.death_tick.is_some());
+
+        // a dead board doesn't move anymore
+        board.tick();
+        board.apply(Action::HardDrop);
+        assert!(events(&mut board).is_empty());
+    }
+
+    #[test]
+    fn tops_out_when_the_spawn_is_blocked() {
+        let mut board = board(Rules {
+            are: 100,
+            ..Rules::tetrio()
+        });
+        board.apply(Action::HardDrop);
+        // the next piece can't be bumped above the blocks
+        for y in board.rules.height..board.blocks.len() {
+            fill(&mut board, y, &[]);
+        }
+
+        for _ in 0..board.rules.spawn_delay_ticks(false) {
+            board.tick();
+        }
+        assert!(events(&mut board)
+            .iter()
+            .any(|x| matches!(x, Event::ToppedOut)));
+        assert!(board.death_tick.is_some());
+    }
+
+    #[test]
+    fn instant_shift_reaches_the_wall_of_a_wide_board() {
+        let rules = Rules {
+            width: 20,
+            ..Rules::tetrio()
+        };
+        let handling = Handling {
+            arr: 0,
+            ..Handling::default()
+        };
+        let rotation = Rc::new(RotationSystem::srs_plus());
+        let mut board = Board::new(1, handling, rotation, Rc::new(rules));
+        // from the left wall, the piece has to cross more than half of the board
+        for _ in 0..10 {
+            board.apply(Action::MoveLeft);
+        }
+        board.press(keys::Key::Right);
+        for _ in 0..handling.das_ticks() + 1 {
+            board.tick();
+        }
+
+        let piece = &board.falling_piece;
+        let right = (0..4)
+            .filter(|x| {
+                piece.get_shape()[*x]
+                    .iter()
+                    .any(|b| matches!(b, Block::Block { .. }))
+            })
+            .map(|x| piece.position.x + x as i32)
+            .max();
+        assert_eq!(right, Some(19));
     }
 }
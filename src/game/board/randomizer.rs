@@ -0,0 +1,243 @@
+use crate::game::tetromino::*;
+use rand::prelude::*;
+
+/// Decides which pieces come next
+pub trait Randomizer {
+    fn next(&mut self, rng: &mut SmallRng) -> Shape;
+}
+
+/// The randomizers which can be chosen in the rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RandomizerKind {
+    /// Every piece once, shuffled
+    SevenBag,
+    /// Every piece twice, shuffled
+    FourteenBag,
+    /// Every piece once and a random one, shuffled
+    SevenPlusOne,
+    /// Any piece, without remembering the previous ones
+    Random,
+    /// The history of the last 4 pieces rerolled up to 6 times against a bag of 35, like TGM3
+    Tgm3,
+    /// A reroll when the piece repeats the previous one, like the NES game
+    Nes,
+}
+
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 6] = [
+        RandomizerKind::SevenBag,
+        RandomizerKind::FourteenBag,
+        RandomizerKind::SevenPlusOne,
+        RandomizerKind::Random,
+        RandomizerKind::Tgm3,
+        RandomizerKind::Nes,
+    ];
+
+    /// The name of the randomizer as stored in files
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7-bag",
+            RandomizerKind::FourteenBag => "14-bag",
+            RandomizerKind::SevenPlusOne => "7+1-bag",
+            RandomizerKind::Random => "random",
+            RandomizerKind::Tgm3 => "tgm3",
+            RandomizerKind::Nes => "nes",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.name() == name)
+    }
+
+    pub fn create(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1, false)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2, false)),
+            RandomizerKind::SevenPlusOne => Box::new(Bag::new(1, true)),
+            RandomizerKind::Random => Box::new(Random),
+            RandomizerKind::Tgm3 => Box::new(Tgm3::new()),
+            RandomizerKind::Nes => Box::new(Nes { last: None }),
+        }
+    }
+}
+
+/// Deals the pieces of a shuffled bag, and fills it again once it is empty
+struct Bag {
+    pack: Vec<Shape>,
+    /// How many times every piece is in the bag
+    copies: usize,
+    /// Whether a random piece is added to the bag
+    extra: bool,
+}
+
+impl Bag {
+    fn new(copies: usize, extra: bool) -> Self {
+        Self {
+            pack: Vec::new(),
+            copies,
+            extra,
+        }
+    }
+
+    fn pack(&self, rng: &mut SmallRng) -> Vec<Shape> {
+        let mut orig = Shape::ALL.repeat(self.copies);
+        if self.extra {
+            orig.push(Shape::ALL[(rng.next_u32() % 7) as usize]);
+        }
+        let mut new = Vec::<Shape>::new();
+        for i in (1..=orig.len() as u32).rev() {
+            new.push(orig.swap_remove((rng.next_u32() % i) as usize));
+        }
+        new
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut SmallRng) -> Shape {
+        if self.pack.is_empty() {
+            self.pack = self.pack(rng);
+        }
+        self.pack.pop().unwrap()
+    }
+}
+
+struct Random;
+
+impl Randomizer for Random {
+    fn next(&mut self, rng: &mut SmallRng) -> Shape {
+        Shape::ALL[(rng.next_u32() % 7) as usize]
+    }
+}
+
+/// See <https://tetris.wiki/TGM_randomizer>
+struct Tgm3 {
+    /// 5 of every piece at first, the pieces which haven't come for the longest time take the
+    /// place of the ones dealt
+    pool: Vec<Shape>,
+    /// The last 4 pieces, the oldest first
+    history: Vec<Shape>,
+    /// The pieces which have come, the one which came the longest time ago first
+    order: Vec<Shape>,
+    started: bool,
+}
+
+impl Tgm3 {
+    fn new() -> Self {
+        Self {
+            pool: Shape::ALL.repeat(5),
+            history: vec![Shape::S, Shape::Z, Shape::S, Shape::Z],
+            order: Vec::new(),
+            started: false,
+        }
+    }
+}
+
+impl Randomizer for Tgm3 {
+    fn next(&mut self, rng: &mut SmallRng) -> Shape {
+        // the first piece is never an S, Z or O
+        if !self.started {
+            self.started = true;
+            let piece = [Shape::I, Shape::J, Shape::L, Shape::T][(rng.next_u32() % 4) as usize];
+            self.history.remove(0);
+            self.history.push(piece);
+            return piece;
+        }
+
+        let mut i = 0;
+        for roll in 0..6 {
+            i = (rng.next_u32() % self.pool.len() as u32) as usize;
+            if !self.history.contains(&self.pool[i]) || roll == 5 {
+                break;
+            }
+            if let Some(x) = self.order.first() {
+                self.pool[i] = *x;
+            }
+        }
+        let piece = self.pool[i];
+
+        self.order.retain(|x| *x != piece);
+        self.order.push(piece);
+        self.pool[i] = self.order[0];
+        self.history.remove(0);
+        self.history.push(piece);
+        piece
+    }
+}
+
+/// See <https://tetris.wiki/Tetris_(NES,_Nintendo)#Randomizer>
+struct Nes {
+    last: Option<Shape>,
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self, rng: &mut SmallRng) -> Shape {
+        // the 8th roll and repeats are rolled again once, without looking at the previous piece
+        let piece = match Shape::ALL.get((rng.next_u32() % 8) as usize) {
+            Some(x) if Some(*x) != self.last => *x,
+            _ => Shape::ALL[(rng.next_u32() % 7) as usize],
+        };
+        self.last = Some(piece);
+        piece
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal(kind: RandomizerKind, seed: u64, n: usize) -> Vec<Shape> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut randomizer = kind.create();
+        (0..n).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    fn count(pieces: &[Shape], shape: Shape) -> usize {
+        pieces.iter().filter(|x| **x == shape).count()
+    }
+
+    #[test]
+    fn bags_deal_every_piece_as_often() {
+        for seed in 0..20 {
+            for bag in deal(RandomizerKind::SevenBag, seed, 70).chunks(7) {
+                for shape in Shape::ALL {
+                    assert_eq!(count(bag, shape), 1, "{:?} in {:?}", shape, bag);
+                }
+            }
+            for bag in deal(RandomizerKind::FourteenBag, seed, 140).chunks(14) {
+                for shape in Shape::ALL {
+                    assert_eq!(count(bag, shape), 2, "{:?} in {:?}", shape, bag);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tgm3_never_starts_with_s_z_or_o() {
+        for seed in 0..200 {
+            let first = deal(RandomizerKind::Tgm3, seed, 1)[0];
+            assert!(
+                !matches!(first, Shape::S | Shape::Z | Shape::O),
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn nes_repeats_only_after_a_reroll() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut nes = RandomizerKind::Nes.create();
+        let mut last = None;
+        for _ in 0..1000 {
+            // the first roll, which is kept unless it is the 8th one or a repeat
+            let first = Shape::ALL
+                .get((rng.clone().next_u32() % 8) as usize)
+                .copied();
+            let piece = nes.next(&mut rng);
+            if first.is_some() && first != last {
+                assert_eq!(Some(piece), first);
+            }
+            last = Some(piece);
+        }
+    }
+}
@@ -21,6 +21,69 @@ pub struct Renderer {
 
     tr: Rc<text::TextRenderer>,
     font: text::Font,
+
+    /// Whether the board being drawn is greyed out
+    grey: bool,
+}
+
+/// The opacity of a board which has been greyed out
+const GREY_OPACITY: f32 = 0.4;
+
+/// The model of the board texture fitted around a playing field of any size. The texture is
+/// drawn for a 10 by 20 field: its cells are repeated, the panels next to them are stretched.
+fn board_model(
+    gh: &mut crate::graphics::GraphicsHandle,
+    width: usize,
+    height: usize,
+) -> graphics::Model {
+    let (w, h) = (width as f32, height as f32);
+    // the parts of the texture, in the units of the field it has been drawn for, and where
+    // they go
+    let mut parts = vec![
+        // the hold slot and the garbage meter
+        ((-6.0, -1.0, 0.0, 16.0), (-6.0, -1.0, 0.0, h - 4.0)),
+        ((-6.0, 16.0, 0.0, 21.0), (-6.0, h - 4.0, 0.0, h + 1.0)),
+        // the queue
+        ((10.0, -1.0, 16.0, 4.0), (w, -1.0, w + 6.0, 4.0)),
+        ((10.0, 4.0, 16.0, 17.0), (w, 4.0, w + 6.0, h - 3.0)),
+        ((10.0, 17.0, 16.0, 21.0), (w, h - 3.0, w + 6.0, h + 1.0)),
+    ];
+    for x in 0..width {
+        // the last column has the border
+        let column = if x + 1 == width { 9 } else { x % 9 } as f32;
+        let x = x as f32;
+        parts.push(((column, -1.0, column + 1.0, 0.0), (x, -1.0, x + 1.0, 0.0)));
+        for y in 0..height - 3 {
+            let row = (y % 17) as f32;
+            let y = y as f32;
+            parts.push((
+                (column, row, column + 1.0, row + 1.0),
+                (x, y, x + 1.0, y + 1.0),
+            ));
+        }
+        // the rows fading out at the top
+        parts.push((
+            (column, 17.0, column + 1.0, 21.0),
+            (x, h - 3.0, x + 1.0, h + 1.0),
+        ));
+    }
+
+    let mut vertices = Vec::new();
+    let mut texcoords = Vec::new();
+    for ((u0, v0, u1, v1), (x0, y0, x1, y1)) in parts {
+        let (u0, u1) = ((u0 + 6.0) / 22.0, (u1 + 6.0) / 22.0);
+        let (v0, v1) = ((21.0 - v0) / 22.0, (21.0 - v1) / 22.0);
+        vertices.extend([
+            (x0, y0, 0.0),
+            (x1, y0, 0.0),
+            (x0, y1, 0.0),
+            (x1, y0, 0.0),
+            (x1, y1, 0.0),
+            (x0, y1, 0.0),
+        ]);
+        texcoords.extend([(u0, v0), (u1, v0), (u0, v1), (u1, v0), (u1, v1), (u0, v1)]);
+    }
+    graphics::Model::new(gh, &vertices, &texcoords, &[]).unwrap()
 }
 
 impl Renderer {
@@ -28,6 +91,7 @@ impl Renderer {
         gh: &mut crate::graphics::GraphicsHandle,
         roman: &crate::resource::ResourceManager,
         tr: Rc<text::TextRenderer>,
+        rules: &Rules,
     ) -> Self {
         let default_vert = roman.get_text("default.vert");
 
@@ -93,27 +157,7 @@ impl Renderer {
         )
         .unwrap();
 
-        let board_model = graphics::Model::new(
-            gh,
-            &[
-                (-6.0, -1.0, 0.0),
-                (16.0, -1.0, 0.0),
-                (-6.0, 21.0, 0.0),
-                (16.0, -1.0, 0.0),
-                (16.0, 21.0, 0.0),
-                (-6.0, 21.0, 0.0),
-            ],
-            &[
-                (0.0, 1.0),
-                (1.0, 1.0),
-                (0.0, 0.0),
-                (1.0, 1.0),
-                (1.0, 0.0),
-                (0.0, 0.0),
-            ],
-            &[],
-        )
-        .unwrap();
+        let board_model = board_model(gh, rules.width, rules.height);
 
         let star_texture = roman.get_image("star.png");
         let star_texture = graphics::Texture::from_image(gh, &star_texture).unwrap();
@@ -136,6 +180,17 @@ impl Renderer {
             star_texture,
             tr,
             font,
+            grey: false,
+        }
+    }
+
+    /// The color of a block, taking a greyed out board into account
+    fn block_color(&self, color: (f32, f32, f32), opacity: f32) -> Vec4 {
+        if self.grey {
+            let luma = (0.3 * color.0 + 0.59 * color.1 + 0.11 * color.2) * 0.6;
+            Vec4::new(luma, luma, luma, opacity)
+        } else {
+            Vec4::new(color.0, color.1, color.2, opacity)
         }
     }
 
@@ -143,30 +198,33 @@ impl Renderer {
         &mut self,
         gh: &mut crate::graphics::GraphicsHandle,
         board: &Board,
+        effects: &BoardEffects,
+        stats: &Stats,
         mut mat: glam::Mat4,
     ) {
         let now = std::time::Instant::now();
-        let death_animation = if let Some(x) = board.death_time {
+        let death_animation = if let Some(x) = effects.death_time {
             now.duration_since(x).as_millis() as f32 / 1000.0
         } else {
             0.0
         } as f32;
-        let opacity = 1.0 - death_animation;
-        let death_fall = death_animation * death_animation * 10.0;
+        self.grey = effects.grey_out && effects.death_time.is_some();
+        let (opacity, death_fall) = if self.grey {
+            (GREY_OPACITY, 0.0)
+        } else {
+            (
+                1.0 - death_animation,
+                death_animation * death_animation * 10.0,
+            )
+        };
 
         mat *= Mat4::from_translation(Vec3::new(0.0, -death_fall, 0.0));
-        mat *= Mat4::from_translation(Vec3::new(5.0, 10.0, 0.0));
-        mat *= Mat4::from_scale(Vec3::new(
-            board.effects.scale,
-            board.effects.scale,
-            board.effects.scale,
-        ));
-        mat *= Mat4::from_translation(Vec3::new(-5.0, -10.0, 0.0));
-        mat *= Mat4::from_translation(Vec3::new(
-            board.effects.position.x,
-            board.effects.position.y,
-            0.0,
-        ));
+        let (width, height) = (board.rules.width as f32, board.rules.height as f32);
+        let center = Vec3::new(width / 2.0, height / 2.0, 0.0);
+        mat *= Mat4::from_translation(center);
+        mat *= Mat4::from_scale(Vec3::new(effects.scale, effects.scale, effects.scale));
+        mat *= Mat4::from_translation(-center);
+        mat *= Mat4::from_translation(Vec3::new(effects.position.x, effects.position.y, 0.0));
 
         {
             gh.bind(self.misc_shader.clone());
@@ -174,15 +232,20 @@ impl Renderer {
             // draw the meter of pieces to be added
             {
                 gh.set_uniform("enable_texture", false);
-                gh.set_uniform("color", glam::Vec4::new(1.0, 0.0, 0.0, opacity));
                 let mut mat = mat * Mat4::from_translation(Vec3::new(-1.0, 0.05, 0.0));
                 for i in &board.lines_received {
+                    // garbage which can't rise yet is dimmer
+                    let waiting = board.tick < i.tick + board.rules.garbage_delay_ticks();
+                    gh.set_uniform(
+                        "color",
+                        glam::Vec4::new(1.0, 0.0, 0.0, if waiting { 0.4 } else { 1.0 } * opacity),
+                    );
                     gh.set_uniform(
                         "view",
-                        mat * Mat4::from_scale(Vec3::new(1.0, *i as f32 - 0.1, 0.9)),
+                        mat * Mat4::from_scale(Vec3::new(1.0, i.lines as f32 - 0.1, 0.9)),
                     );
                     self.block_model.render(gh);
-                    mat *= Mat4::from_translation(Vec3::new(0.0, *i as f32, 0.0));
+                    mat *= Mat4::from_translation(Vec3::new(0.0, i.lines as f32, 0.0));
                 }
             }
 
@@ -201,53 +264,83 @@ impl Renderer {
         {
             gh.bind(self.block_shader.clone());
             self.block_texture.bind(gh);
-            // draw the placed pieces
-            for i in 0..24 {
-                for j in 0..10 {
-                    if let Block::Block { color } = board.blocks[i][j] {
+            // draw the placed pieces, with the cleared lines until the line clear delay is over
+            let clearing = board.clearing.as_ref().filter(|x| board.tick < x.end);
+            let blocks = clearing.map_or(&board.blocks, |x| &x.blocks);
+            for (i, row) in blocks
+                .iter()
+                .take(board.rules.height + DRAWN_ROWS)
+                .enumerate()
+            {
+                let (mut y, mut color_override) = (i as f32, None);
+                if let Some(x) = clearing {
+                    let progress = (board.tick - x.start) as f32 / (x.end - x.start) as f32;
+                    if x.rows.contains(&i) {
+                        // the cleared lines flash, then disappear
+                        if progress >= 0.5 {
+                            continue;
+                        }
+                        if ((board.tick - x.start) / 4).is_multiple_of(2) {
+                            color_override = Some((1.0, 1.0, 1.0));
+                        }
+                    } else if progress >= 0.5 {
+                        // and the lines above collapse
+                        let below = x.rows.iter().filter(|row| **row < i).count();
+                        y -= below as f32 * ((progress - 0.5) * 2.0).min(1.0);
+                    }
+                }
+                for (j, block) in row.iter().enumerate() {
+                    if let Block::Block { color } = *block {
                         gh.set_uniform(
                             "view",
-                            mat * glam::Mat4::from_translation(Vec3::new(j as f32, i as f32, 0.0)),
+                            mat * glam::Mat4::from_translation(Vec3::new(j as f32, y, 0.0)),
                         );
                         gh.set_uniform(
                             "color",
-                            glam::Vec4::new(color.0, color.1, color.2, opacity),
+                            self.block_color(color_override.unwrap_or(color), opacity),
                         );
                         self.block_model.render(gh);
                     }
                 }
             }
 
-            // draw the ghost piece
-            self.draw_piece(
-                gh,
-                mat * glam::Mat4::from_translation(Vec3::new(
-                    board.ghost_piece.position.x as f32,
-                    board.ghost_piece.position.y as f32,
-                    0.0,
-                )),
-                &board.ghost_piece,
-                true,
-            );
+            // there is no piece while waiting for the next one to spawn
+            if board.spawn_tick.is_none() {
+                // draw the ghost piece
+                self.draw_piece(
+                    gh,
+                    mat * glam::Mat4::from_translation(Vec3::new(
+                        board.ghost_piece.position.x as f32,
+                        board.ghost_piece.position.y as f32,
+                        0.0,
+                    )),
+                    &board.ghost_piece,
+                    true,
+                );
 
-            // draw the falling piece
-            self.draw_piece(
-                gh,
-                mat * glam::Mat4::from_translation(Vec3::new(
-                    board.falling_piece.position.x as f32,
-                    board.falling_piece.position.y as f32,
-                    0.0,
-                )),
-                &board.falling_piece,
-                false,
-            );
+                // draw the falling piece
+                self.draw_piece(
+                    gh,
+                    mat * glam::Mat4::from_translation(Vec3::new(
+                        board.falling_piece.position.x as f32,
+                        board.falling_piece.position.y as f32,
+                        0.0,
+                    )),
+                    &board.falling_piece,
+                    false,
+                );
+            }
 
             // draw the queue
             {
-                let mut mat = mat * Mat4::from_translation(Vec3::new(12.5, 17.5, 0.0));
-                for i in 0..5 {
-                    self.draw_shape(gh, mat, board.piece_generator.queue[i], false);
-                    mat *= Mat4::from_translation(Vec3::new(0.0, -3.0, 0.0));
+                let mut mat =
+                    mat * Mat4::from_translation(Vec3::new(width + 2.5, height - 2.5, 0.0));
+                // more than 5 pieces are moved closer together to fit next to the playing field
+                let queue = &board.piece_generator.queue;
+                let step = (15.0 / queue.len() as f32).min(3.0);
+                for shape in queue {
+                    self.draw_shape(gh, mat, *shape, &board.rotation, false);
+                    mat *= Mat4::from_translation(Vec3::new(0.0, -step, 0.0));
                 }
             }
 
@@ -255,8 +348,9 @@ impl Renderer {
             if let Some(x) = board.swap_piece {
                 self.draw_shape(
                     gh,
-                    mat * Mat4::from_translation(Vec3::new(-2.5, 17.5, 0.0)),
+                    mat * Mat4::from_translation(Vec3::new(-2.5, height - 2.5, 0.0)),
                     x,
+                    &board.rotation,
                     board.swapped,
                 );
             }
@@ -265,14 +359,17 @@ impl Renderer {
         }
 
         // draw the info text
-        if let Some(x) = &board.effects.info {
-            let width = self.tr.get_width(gh, &mut self.font, x.text.as_str());
+        if let Some(x) = &effects.info {
+            let text_width = self.tr.get_width(gh, &mut self.font, x.text.as_str());
             let size = 1.0 + now.duration_since(x.time).as_millis() as f32 / 4000.0;
             self.tr.draw(
                 gh,
                 &mut self.font,
-                mat * Mat4::from_translation(Vec3::new(-1.2 - width * 0.01 * size, 14.0, 0.0))
-                    * Mat4::from_scale(Vec3::new(0.01, 0.01, 0.01) * size),
+                mat * Mat4::from_translation(Vec3::new(
+                    -1.2 - text_width * 0.01 * size,
+                    height - 6.0,
+                    0.0,
+                )) * Mat4::from_scale(Vec3::new(0.01, 0.01, 0.01) * size),
                 Vec4::new(
                     1.0,
                     1.0,
@@ -286,7 +383,7 @@ impl Renderer {
         self.tr.draw(
             gh,
             &mut self.font,
-            mat * Mat4::from_translation(Vec3::new(10.2, 2.3, 0.0))
+            mat * Mat4::from_translation(Vec3::new(width + 0.2, 2.3, 0.0))
                 * Mat4::from_scale(Vec3::new(0.008, 0.01, 0.01)),
             Vec4::new(1.0, 1.0, 1.0, if board.score.combo < 2 { 0.1 } else { 1.0 }),
             format!("COMBO×{}", board.score.combo).as_str(),
@@ -295,16 +392,60 @@ impl Renderer {
         self.tr.draw(
             gh,
             &mut self.font,
-            mat * Mat4::from_translation(Vec3::new(10.2, 0.3, 0.0))
+            mat * Mat4::from_translation(Vec3::new(width + 0.2, 0.3, 0.0))
                 * Mat4::from_scale(Vec3::new(0.01, 0.01, 0.01)),
             Vec4::new(1.0, 1.0, 1.0, if board.score.b2b < 2 { 0.1 } else { 1.0 }),
             format!("B2B×{}", board.score.b2b).as_str(),
         );
 
+        // the statistics under the hold slot
+        for (i, (name, value)) in stats.summary().iter().enumerate() {
+            let text = format!("{} {}", name.to_uppercase(), value);
+            let width = self.tr.get_width(gh, &mut self.font, &text);
+            self.tr.draw(
+                gh,
+                &mut self.font,
+                mat * Mat4::from_translation(Vec3::new(
+                    -1.2 - width * 0.006,
+                    height - 9.0 - i as f32 * 1.1,
+                    0.0,
+                )) * Mat4::from_scale(Vec3::new(0.006, 0.007, 0.007)),
+                Vec4::new(1.0, 1.0, 1.0, 0.6),
+                &text,
+            );
+        }
+
+        // and the score with the level on the other side of the board
+        let level = format!("LEVEL {}", board.score.level());
+        let width = self.tr.get_width(gh, &mut self.font, &level);
+        self.tr.draw(
+            gh,
+            &mut self.font,
+            mat * Mat4::from_translation(Vec3::new(-1.2 - width * 0.008, 2.3, 0.0))
+                * Mat4::from_scale(Vec3::new(0.008, 0.01, 0.01)),
+            Vec4::new(1.0, 1.0, 1.0, 0.6),
+            &level,
+        );
+        let score = board.score.score.to_string();
+        let width = self.tr.get_width(gh, &mut self.font, &score);
+        self.tr.draw(
+            gh,
+            &mut self.font,
+            mat * Mat4::from_translation(Vec3::new(-1.2 - width * 0.01, 0.3, 0.0))
+                * Mat4::from_scale(Vec3::new(0.01, 0.01, 0.01)),
+            Vec4::new(
+                1.0,
+                1.0,
+                1.0,
+                if board.score.score == 0 { 0.1 } else { 1.0 },
+            ),
+            &score,
+        );
+
         // draw the particles
         {
             gh.bind(self.misc_shader.clone());
-            for i in &board.effects.particles {
+            for i in &effects.particles {
                 gh.set_uniform(
                     "view",
                     mat * Mat4::from_translation(Vec3::new(i.position.x, i.position.y, 0.0))
@@ -344,9 +485,10 @@ impl Renderer {
         gh: &mut crate::graphics::GraphicsHandle,
         mat: Mat4,
         shape: tetromino::Shape,
+        system: &Rc<RotationSystem>,
         shadow: bool,
     ) {
-        let piece = Tetromino::new(shape);
+        let piece = Tetromino::new(shape, system);
         let shape = piece.get_shape();
         let mut com = glam::Vec2::new(0.0, 0.0);
 
@@ -386,7 +528,7 @@ impl Renderer {
                         if shadow {
                             glam::Vec4::new(0.5, 0.5, 0.5, 0.5)
                         } else {
-                            glam::Vec4::new(color.0, color.1, color.2, 1.0)
+                            self.block_color(color, if self.grey { GREY_OPACITY } else { 1.0 })
                         },
                     );
                     self.block_model.render(gh);
@@ -0,0 +1,412 @@
+use super::RandomizerKind;
+
+/// The names of the rulesets built into the game
+pub const PRESETS: [&str; 3] = ["tetr.io", "guideline", "ppt"];
+
+/// The rows above the visible playing field, where pieces spawn and can still be placed
+const BUFFER_ROWS: usize = 12;
+
+/// The directory where custom rulesets are looked for, one JSON file per ruleset
+pub fn custom_dir() -> std::path::PathBuf {
+    crate::config_dir().join("rules")
+}
+
+/// Which pieces other than T can spin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllSpin {
+    /// Only T-spins count
+    Off,
+    /// Spins of the other pieces count as minis
+    Mini,
+    /// Spins of the other pieces count as full spins
+    Full,
+}
+
+impl AllSpin {
+    pub const ALL: [AllSpin; 3] = [AllSpin::Off, AllSpin::Mini, AllSpin::Full];
+
+    /// The name of the option as stored in files
+    pub fn name(self) -> &'static str {
+        match self {
+            AllSpin::Off => "off",
+            AllSpin::Mini => "mini",
+            AllSpin::Full => "full",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.name() == name)
+    }
+}
+
+/// How clearing lines with consecutive pieces adds to the attack
+#[derive(Clone, Debug, PartialEq)]
+pub enum Combo {
+    /// The lines added for every length of the combo, the last one is used for longer combos
+    Table(Vec<u32>),
+    /// Like tetr.io: the attack is multiplied by `1 + x` for every piece of the combo, clears
+    /// which wouldn't send anything send lines growing with the logarithm of the combo
+    Multiplier(f64),
+}
+
+impl Combo {
+    /// The attack of a clear worth `lines` after `combo` clears in a row
+    pub fn apply(&self, lines: u32, combo: u32) -> u32 {
+        match self {
+            Combo::Table(table) => {
+                lines
+                    + table
+                        .get(combo as usize)
+                        .or(table.last())
+                        .copied()
+                        .unwrap_or(0)
+            }
+            Combo::Multiplier(x) if lines > 0 => (lines as f64 * (1.0 + x * combo as f64)) as u32,
+            Combo::Multiplier(_) => (1.0 + 1.25 * combo as f64).ln() as u32,
+        }
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        match self {
+            Combo::Table(x) => x.clone().into(),
+            Combo::Multiplier(x) => json::object! { multiplier: *x },
+        }
+    }
+
+    fn from_json(data: &json::JsonValue) -> Option<Self> {
+        if data.is_array() {
+            Some(Combo::Table(
+                data.members().map(|x| x.as_u32()).collect::<Option<_>>()?,
+            ))
+        } else {
+            Some(Combo::Multiplier(data["multiplier"].as_f64()?))
+        }
+    }
+}
+
+/// The rules every board of a game is played by: what is recognized, how many lines it sends
+/// and how the garbage rises
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub name: String,
+    /// Whether S, Z, L, J and I pieces which can't move after a rotation are spins
+    pub all_spin: AllSpin,
+    /// Whether clearing the whole playing field sends extra lines
+    pub perfect_clear: bool,
+
+    /// The lines sent for clearing 1 to 4 lines without a spin
+    pub clears: [u32; 4],
+    /// The lines sent for full spins clearing 1 to 3 lines
+    pub spins: [u32; 3],
+    /// The lines sent for mini spins clearing 1 or 2 lines
+    pub minis: [u32; 2],
+    /// The lines sent for a perfect clear, on top of the lines of the clear itself
+    pub perfect_clear_bonus: u32,
+    pub combo: Combo,
+    /// The lines added to back-to-back clears: from which length of the chain on, how many,
+    /// sorted by the length. A single entry means the bonus doesn't grow with the chain.
+    pub b2b: Vec<(u32, u32)>,
+
+    /// The chance of the hole moving to another column between two lines of the same attack
+    pub messiness: f64,
+    /// How long garbage waits before it can rise, in milliseconds
+    pub garbage_delay: u64,
+    /// The most lines which can rise after a single piece, the rest waits for the next one
+    pub garbage_cap: Option<u32>,
+    /// Whether the lines sent first cancel the garbage waiting to rise
+    pub cancel: bool,
+    /// Whether garbage also rises after pieces which clear lines
+    pub garbage_on_clear: bool,
+
+    /// How long the next piece waits before it spawns (ARE), in milliseconds
+    pub are: u64,
+    /// How long cleared lines are shown before they disappear, in milliseconds. The next piece
+    /// waits for them on top of the ARE.
+    pub line_clear_delay: u64,
+
+    /// The number of columns of the playing field
+    pub width: usize,
+    /// The number of visible rows of the playing field
+    pub height: usize,
+
+    /// Which pieces come next
+    pub randomizer: RandomizerKind,
+    /// The number of next pieces shown
+    pub preview: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::tetrio()
+    }
+}
+
+impl Rules {
+    /// The widths a playing field can have, it has to fit the I piece
+    pub const WIDTHS: std::ops::RangeInclusive<usize> = 4..=20;
+    /// The visible heights a playing field can have, the queue has to fit next to it
+    pub const HEIGHTS: std::ops::RangeInclusive<usize> = 20..=40;
+    /// The most next pieces which can be shown
+    pub const MAX_PREVIEW: usize = 7;
+
+    pub fn tetrio() -> Self {
+        Self {
+            name: String::from("tetr.io"),
+            all_spin: AllSpin::Off,
+            perfect_clear: true,
+            clears: [0, 1, 2, 4],
+            spins: [2, 4, 6],
+            minis: [0, 1],
+            perfect_clear_bonus: 10,
+            combo: Combo::Multiplier(0.25),
+            b2b: vec![
+                (1, 1),
+                (3, 2),
+                (8, 3),
+                (24, 4),
+                (67, 5),
+                (185, 6),
+                (504, 7),
+                (1370, 8),
+            ],
+            messiness: 0.05,
+            garbage_delay: 333,
+            garbage_cap: Some(8),
+            cancel: true,
+            garbage_on_clear: false,
+            are: 0,
+            line_clear_delay: 0,
+            width: 10,
+            height: 20,
+            randomizer: RandomizerKind::SevenBag,
+            preview: 5,
+        }
+    }
+
+    /// The rules of the guideline games, like Tetris 99
+    pub fn guideline() -> Self {
+        Self {
+            name: String::from("guideline"),
+            all_spin: AllSpin::Off,
+            perfect_clear: true,
+            clears: [0, 1, 2, 4],
+            spins: [2, 4, 6],
+            minis: [0, 1],
+            perfect_clear_bonus: 10,
+            combo: Combo::Table(vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5]),
+            b2b: vec![(1, 1)],
+            messiness: 0.0,
+            garbage_delay: 0,
+            garbage_cap: None,
+            cancel: true,
+            garbage_on_clear: false,
+            are: 0,
+            line_clear_delay: 0,
+            width: 10,
+            height: 20,
+            randomizer: RandomizerKind::SevenBag,
+            preview: 5,
+        }
+    }
+
+    /// The rules of Puyo Puyo Tetris, which only has a slower combo table
+    pub fn ppt() -> Self {
+        Self {
+            name: String::from("ppt"),
+            combo: Combo::Table(vec![0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5]),
+            ..Self::guideline()
+        }
+    }
+
+    /// Get a preset or a custom ruleset from [`custom_dir`]
+    pub fn by_name(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match name {
+            "tetr.io" => Ok(Self::tetrio()),
+            "guideline" => Ok(Self::guideline()),
+            "ppt" => Ok(Self::ppt()),
+            name => {
+                let data = std::fs::read_to_string(custom_dir().join(format!("{}.json", name)))?;
+                Ok(Self::from_json(name, &json::parse(&data)?)?)
+            }
+        }
+    }
+
+    /// The names of all the rulesets which can be chosen, the custom ones last
+    pub fn available() -> Vec<String> {
+        let mut names = PRESETS.map(String::from).to_vec();
+        let mut custom = std::fs::read_dir(custom_dir())
+            .map(|x| {
+                x.flatten()
+                    .filter_map(|x| {
+                        let path = x.path();
+                        if path.extension()? != "json" {
+                            return None;
+                        }
+                        Some(path.file_stem()?.to_str()?.to_string())
+                    })
+                    .filter(|x| !PRESETS.contains(&x.as_str()))
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        custom.sort();
+        names.extend(custom);
+        names
+    }
+
+    /// The lines added to a back-to-back clear, `chain` is the number of difficult clears
+    /// before it
+    pub fn b2b_bonus(&self, chain: u32) -> u32 {
+        self.b2b
+            .iter()
+            .rev()
+            .find(|(from, _)| chain >= *from)
+            .map_or(0, |(_, lines)| *lines)
+    }
+
+    /// The number of ticks garbage waits before it can rise
+    pub fn garbage_delay_ticks(&self) -> u64 {
+        crate::ms_to_ticks(self.garbage_delay)
+    }
+
+    /// The number of ticks the next piece waits after a piece has been locked
+    pub fn spawn_delay_ticks(&self, lines_cleared: bool) -> u64 {
+        crate::ms_to_ticks(self.are)
+            + if lines_cleared {
+                self.line_clear_delay_ticks()
+            } else {
+                0
+            }
+    }
+
+    pub fn line_clear_delay_ticks(&self) -> u64 {
+        crate::ms_to_ticks(self.line_clear_delay)
+    }
+
+    /// The number of rows of the playing field, including the hidden ones above the visible rows
+    pub fn rows(&self) -> usize {
+        self.height + BUFFER_ROWS
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            name: self.name.as_str(),
+            all_spin: self.all_spin.name(),
+            perfect_clear: self.perfect_clear,
+            attack: {
+                clears: self.clears.to_vec(),
+                spins: self.spins.to_vec(),
+                minis: self.minis.to_vec(),
+                perfect_clear: self.perfect_clear_bonus,
+            },
+            combo: self.combo.to_json(),
+            b2b: self.b2b.iter().map(|(from, lines)| json::array![*from, *lines]).collect::<Vec<json::JsonValue>>(),
+            messiness: self.messiness,
+            garbage_delay: self.garbage_delay,
+            garbage_cap: self.garbage_cap,
+            cancel: self.cancel,
+            garbage_on_clear: self.garbage_on_clear,
+            are: self.are,
+            line_clear_delay: self.line_clear_delay,
+            width: self.width,
+            height: self.height,
+            randomizer: self.randomizer.name(),
+            preview: self.preview,
+        }
+    }
+
+    /// Read a ruleset written by [`Rules::to_json`] or a custom one. Everything it doesn't
+    /// mention is taken from the preset named in `base`, or in `name` for saved rulesets.
+    pub fn from_json(name: &str, data: &json::JsonValue) -> Result<Self, String> {
+        let base = match data["base"].as_str() {
+            Some(x) if !PRESETS.contains(&x) => return Err(format!("Unknown base ruleset {}", x)),
+            Some(x) => x,
+            None => data["name"]
+                .as_str()
+                .filter(|x| PRESETS.contains(x))
+                .unwrap_or("tetr.io"),
+        };
+        let mut rules = Self::by_name(base).map_err(|e| e.to_string())?;
+        rules.name = String::from(name);
+
+        let invalid = |key: &str| format!("Invalid {} in ruleset {}", key, name);
+        if let Some(x) = data["all_spin"].as_str() {
+            rules.all_spin = AllSpin::from_name(x).ok_or(invalid("all_spin"))?;
+        }
+        if let Some(x) = data["perfect_clear"].as_bool() {
+            rules.perfect_clear = x;
+        }
+        let attack = &data["attack"];
+        if attack.has_key("clears") {
+            rules.clears = table(&attack["clears"]).ok_or(invalid("attack.clears"))?;
+        }
+        if attack.has_key("spins") {
+            rules.spins = table(&attack["spins"]).ok_or(invalid("attack.spins"))?;
+        }
+        if attack.has_key("minis") {
+            rules.minis = table(&attack["minis"]).ok_or(invalid("attack.minis"))?;
+        }
+        if let Some(x) = attack["perfect_clear"].as_u32() {
+            rules.perfect_clear_bonus = x;
+        }
+        if data.has_key("combo") {
+            rules.combo = Combo::from_json(&data["combo"]).ok_or(invalid("combo"))?;
+        }
+        if data.has_key("b2b") {
+            rules.b2b = data["b2b"]
+                .members()
+                .map(|x| Some((x[0].as_u32()?, x[1].as_u32()?)))
+                .collect::<Option<_>>()
+                .ok_or(invalid("b2b"))?;
+        }
+        if let Some(x) = data["messiness"].as_f64() {
+            rules.messiness = x.clamp(0.0, 1.0);
+        }
+        if let Some(x) = data["garbage_delay"].as_u64() {
+            rules.garbage_delay = x;
+        }
+        if data.has_key("garbage_cap") {
+            // null means there is no cap
+            rules.garbage_cap = data["garbage_cap"].as_u32();
+        }
+        if let Some(x) = data["cancel"].as_bool() {
+            rules.cancel = x;
+        }
+        if let Some(x) = data["garbage_on_clear"].as_bool() {
+            rules.garbage_on_clear = x;
+        }
+        if let Some(x) = data["are"].as_u64() {
+            rules.are = x;
+        }
+        if let Some(x) = data["line_clear_delay"].as_u64() {
+            rules.line_clear_delay = x;
+        }
+        if data.has_key("width") {
+            rules.width = data["width"]
+                .as_usize()
+                .filter(|x| Self::WIDTHS.contains(x))
+                .ok_or(invalid("width"))?;
+        }
+        if data.has_key("height") {
+            rules.height = data["height"]
+                .as_usize()
+                .filter(|x| Self::HEIGHTS.contains(x))
+                .ok_or(invalid("height"))?;
+        }
+        if let Some(x) = data["randomizer"].as_str() {
+            rules.randomizer = RandomizerKind::from_name(x).ok_or(invalid("randomizer"))?;
+        }
+        if let Some(x) = data["preview"].as_usize() {
+            rules.preview = x.min(Self::MAX_PREVIEW);
+        }
+        Ok(rules)
+    }
+}
+
+/// Read an attack table of a fixed length
+fn table<const N: usize>(data: &json::JsonValue) -> Option<[u32; N]> {
+    data.members()
+        .map(|x| x.as_u32())
+        .collect::<Option<Vec<u32>>>()?
+        .try_into()
+        .ok()
+}
@@ -1,13 +1,56 @@
+use super::Rules;
 use crate::game::tetromino;
 
+/// What kind of spin a piece has been locked with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Spin {
+    None,
+    /// A T-spin with only one of the corners in front of the T filled, or a spin of another
+    /// piece with [`super::AllSpin::Mini`]
+    Mini,
+    Full,
+}
+
+/// The lines needed to get to the next level
+const LINES_PER_LEVEL: u32 = 10;
+/// The fastest gravity, 20 rows per frame, in milliseconds per row
+const MAX_GRAVITY: f64 = 1000.0 / 60.0 / 20.0;
+
 pub struct ScoreHandler {
     pub combo: u32,
     pub b2b: u32,
+    /// The number of lines cleared since the start of the game
+    pub lines: u32,
+    /// The points scored since the start of the game
+    pub score: u64,
 }
 
 impl ScoreHandler {
     pub fn new() -> Self {
-        Self { combo: 0, b2b: 0 }
+        Self {
+            combo: 0,
+            b2b: 0,
+            lines: 0,
+            score: 0,
+        }
+    }
+
+    /// The current level, starting at 1
+    pub fn level(&self) -> u32 {
+        self.lines / LINES_PER_LEVEL + 1
+    }
+
+    /// The time it takes a piece to fall by one row on the current level, in milliseconds,
+    /// following the curve of the guideline up to 20G, which is reached by level 20
+    pub fn gravity(&self) -> f64 {
+        // the base of the curve turns negative past level 115
+        let level = self.level().min(20) as f64;
+        ((0.8 - (level - 1.0) * 0.007).powf(level - 1.0) * 1000.0).max(MAX_GRAVITY)
+    }
+
+    /// Score a piece dropped by `rows` rows, hard drops are worth twice as much
+    pub fn drop(&mut self, rows: u32, hard: bool) {
+        self.score += rows as u64 * if hard { 2 } else { 1 };
     }
 
     /// Analyze the move taken and return the number of lines to be sent
@@ -18,69 +61,102 @@ impl ScoreHandler {
     ///
     /// * `cleared` - the number of lines cleared
     /// * `piece` - the shape of the piece which cleared the line
-    /// * `covered` - whether the piece was obstrued from the top
+    /// * `spin` - the kind of spin the piece has been locked with
+    /// * `perfect_clear` - whether the playing field is empty after clearing the lines
+    /// * `rules` - the attack tables
     pub fn analyze(
         &mut self,
         cleared: u32,
         piece: tetromino::Shape,
-        covered: bool,
+        spin: Spin,
+        perfect_clear: bool,
+        rules: &Rules,
     ) -> (u32, Option<String>) {
-        // a T-spin occurs when a T is placed where it would otherwise be obstructed
-        let tspin = if let tetromino::Shape::T = piece {
-            covered
-        } else {
-            false
+        // a mini triple can't really happen, it counts as a full spin
+        let spin = match spin {
+            Spin::Mini if cleared >= 3 => Spin::Full,
+            x => x,
         };
-
-        // Icrement combo counter when clearing lines, reset it otherwise
-        if cleared > 0 {
-            self.combo += 1;
-            // and modify b2b only when lines were cleared
-            if cleared == 4 || tspin {
-                self.b2b += 1;
-            } else {
-                self.b2b = 0;
-            }
-        } else {
+        // the points are multiplied by the level the piece has been dropped on
+        let level = self.level() as u64;
+        self.lines += cleared;
+        if cleared == 0 {
+            self.score += points(cleared, spin) * level;
+            // Reset the combo counter when no lines have been cleared
             self.combo = 0;
+            return (0, None);
         }
 
-        let b2b_bonus = if self.b2b > 0 {
-            ((self.b2b - 1) as f32).sqrt().floor() as u32
+        let spin_name = format!("{}-SPIN", piece.letter());
+        let (lines, message) = match (spin, cleared) {
+            (_, 4) => (rules.clears[3], String::from("TETRIS")),
+            (Spin::Mini, 1) => (rules.minis[0], format!("{} MINI", spin_name)),
+            (Spin::Mini, x) => (
+                rules.minis[x as usize - 1],
+                format!("{} MINI DOUBLE", spin_name),
+            ),
+            (Spin::Full, x) => (
+                rules.spins[x as usize - 1],
+                format!("{} {}", spin_name, clear_name(x)),
+            ),
+            (Spin::None, x) => (rules.clears[x as usize - 1], String::from(clear_name(x))),
+        };
+
+        // Icrement combo counter when clearing lines
+        self.combo += 1;
+        // and b2b when the clear was difficult
+        let difficult = cleared == 4 || spin != Spin::None;
+        let points = points(cleared, spin) * level;
+        self.score += if difficult && self.b2b > 0 {
+            points * 3 / 2
         } else {
-            0
+            points
         };
+        self.score += 50 * (self.combo - 1) as u64 * level;
+        let lines = if difficult {
+            self.b2b += 1;
+            lines + rules.b2b_bonus(self.b2b - 1)
+        } else {
+            self.b2b = 0;
+            lines
+        };
+        let lines = rules.combo.apply(lines, self.combo - 1);
 
-        match cleared {
-            0 => (0, None),
-            4 => (3 + self.combo + b2b_bonus, Some(String::from("TETRIS"))),
-            x => {
-                if tspin {
-                    match cleared {
-                        1 => (
-                            2 + self.combo / 2 + b2b_bonus,
-                            Some(String::from("T-SPIN SINGLE")),
-                        ),
-                        2 => (
-                            4 + self.combo / 2 + b2b_bonus,
-                            Some(String::from("T-SPIN DOUBLE")),
-                        ),
-                        3 => (
-                            6 + self.combo / 2 + b2b_bonus,
-                            Some(String::from("T-SPIN TRIPLE")),
-                        ),
-                        _ => (0, None),
-                    }
-                } else {
-                    let x = x - 1 + self.combo / 2 + b2b_bonus;
-                    match cleared {
-                        1 => (x, Some(String::from("SINGLE"))),
-                        2 => (x, Some(String::from("DOUBLE"))),
-                        3 => (x, Some(String::from("TRIPLE"))),
-                        _ => (x, None),
-                    }
-                }
-            }
+        if perfect_clear {
+            (
+                lines + rules.perfect_clear_bonus,
+                Some(String::from("PERFECT CLEAR")),
+            )
+        } else {
+            (lines, Some(message))
         }
     }
 }
+
+/// The points scored for clearing lines on level 1, like in the guideline
+fn points(cleared: u32, spin: Spin) -> u64 {
+    match (spin, cleared) {
+        (Spin::None, 0) => 0,
+        (Spin::None, 1) => 100,
+        (Spin::None, 2) => 300,
+        (Spin::None, 3) => 500,
+        (Spin::Mini, 0) => 100,
+        (Spin::Mini, 1) => 200,
+        (Spin::Mini, 2) => 400,
+        (_, 0) => 400,
+        (_, 1) => 800,
+        (_, 2) => 1200,
+        (_, 3) => 1600,
+        (_, _) => 800,
+    }
+}
+
+/// What clearing a number of lines is called
+fn clear_name(lines: u32) -> &'static str {
+    match lines {
+        1 => "SINGLE",
+        2 => "DOUBLE",
+        3 => "TRIPLE",
+        _ => "TETRIS",
+    }
+}
@@ -0,0 +1,110 @@
+use super::*;
+
+/// Encode a block as a single character, `.` for air and `G` for garbage
+fn block_letter(block: &Block) -> char {
+    match block {
+        Block::Air => '.',
+        Block::Block { color } => tetromino::Shape::ALL
+            .iter()
+            .find(|x| x.color() == *color)
+            .map_or('G', |x| x.letter()),
+    }
+}
+
+fn letter_block(letter: char) -> Block {
+    match letter {
+        '.' => Block::Air,
+        x => Block::Block {
+            color: tetromino::Shape::from_letter(x).map_or(GARBAGE_COLOR, |x| x.color()),
+        },
+    }
+}
+
+fn piece_json(piece: &Tetromino) -> json::JsonValue {
+    json::object! {
+        shape: piece.shape.letter().to_string(),
+        x: piece.position.x,
+        y: piece.position.y,
+        rotation: piece.rotation_state(),
+    }
+}
+
+fn json_piece(data: &json::JsonValue, system: &Rc<RotationSystem>) -> Option<Tetromino> {
+    Some(Tetromino::placed(
+        json_shape(&data["shape"])?,
+        BlockPos::new(data["x"].as_i32()?, data["y"].as_i32()?),
+        data["rotation"].as_u8()?,
+        system,
+    ))
+}
+
+fn json_shape(data: &json::JsonValue) -> Option<tetromino::Shape> {
+    tetromino::Shape::from_letter(data.as_str()?.chars().next()?)
+}
+
+impl Board {
+    /// Describe everything that's needed to draw the board
+    pub fn snapshot(&self) -> json::JsonValue {
+        json::object! {
+            blocks: self.blocks
+                .iter()
+                .take(self.rules.height + DRAWN_ROWS)
+                .map(|row| row.iter().map(block_letter).collect::<String>())
+                .collect::<Vec<String>>(),
+            falling: piece_json(&self.falling_piece),
+            waiting: self.spawn_tick.is_some(),
+            hold: self.swap_piece.map(|x| x.letter().to_string()),
+            swapped: self.swapped,
+            queue: self.piece_generator
+                .queue
+                .iter()
+                .map(|x| x.letter().to_string())
+                .collect::<Vec<String>>(),
+            garbage: self.lines_received.iter().map(|x| x.lines).collect::<Vec<u32>>(),
+            combo: self.score.combo,
+            b2b: self.score.b2b,
+            score: self.score.score,
+            lines: self.score.lines,
+            dead: self.death_tick.is_some(),
+        }
+    }
+
+    /// Make the board look like a snapshot taken by [`Board::snapshot`]
+    ///
+    /// Only the visible state is restored, a restored board can't be simulated any further.
+    pub fn restore(&mut self, data: &json::JsonValue) -> Option<()> {
+        for (row, line) in self.blocks.iter_mut().zip(data["blocks"].members()) {
+            for (block, letter) in row.iter_mut().zip(line.as_str()?.chars()) {
+                *block = letter_block(letter);
+            }
+        }
+        self.falling_piece = json_piece(&data["falling"], &self.rotation)?;
+        // a restored board never ticks, so the piece is hidden until the next snapshot
+        self.spawn_tick = data["waiting"].as_bool()?.then_some(u64::MAX);
+        self.swap_piece = json_shape(&data["hold"]);
+        self.swapped = data["swapped"].as_bool()?;
+        self.piece_generator.queue = data["queue"]
+            .members()
+            .map(json_shape)
+            .collect::<Option<_>>()?;
+        self.lines_received = data["garbage"]
+            .members()
+            .map(|x| {
+                Some(Garbage {
+                    lines: x.as_u32()?,
+                    tick: 0,
+                })
+            })
+            .collect::<Option<_>>()?;
+        self.score.combo = data["combo"].as_u32()?;
+        self.score.b2b = data["b2b"].as_u32()?;
+        self.score.score = data["score"].as_u64()?;
+        self.score.lines = data["lines"].as_u32()?;
+        if data["dead"].as_bool()? && self.death_tick.is_none() {
+            self.death_tick = Some(self.tick);
+            self.events.push(Event::ToppedOut);
+        }
+        self.update_ghost();
+        Some(())
+    }
+}
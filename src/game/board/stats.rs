@@ -0,0 +1,88 @@
+use super::Event;
+
+/// How fast and how well a board is played, counted from its events
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// The number of ticks the board has been alive for
+    ticks: u64,
+    pieces: u32,
+    /// The lines sent, including the ones which cancelled received garbage
+    attack: u32,
+    /// The garbage lines cleared
+    garbage_cleared: u32,
+    keys: u32,
+    pub lines: u32,
+    pub max_combo: u32,
+    /// The number of pieces placed with more key presses than needed
+    pub finesse_faults: u32,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a tick of the board being alive
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn handle(&mut self, event: &Event) {
+        match event {
+            Event::KeyPressed => self.keys += 1,
+            Event::PieceLocked(_) => self.pieces += 1,
+            Event::LinesCleared {
+                rows,
+                combo,
+                garbage,
+                attack,
+            } => {
+                self.lines += rows.len() as u32;
+                self.max_combo = self.max_combo.max(*combo);
+                self.garbage_cleared += garbage;
+                self.attack += attack;
+            }
+            Event::FinesseFault => self.finesse_faults += 1,
+            _ => (),
+        }
+    }
+
+    /// The time the board has been alive for, in seconds
+    fn seconds(&self) -> f64 {
+        // don't show huge numbers in the first ticks
+        (self.ticks as f64 / crate::TICKS_PER_SECOND as f64).max(1.0)
+    }
+
+    /// Pieces per second
+    pub fn pps(&self) -> f64 {
+        self.pieces as f64 / self.seconds()
+    }
+
+    /// Attack per minute
+    pub fn apm(&self) -> f64 {
+        self.attack as f64 * 60.0 / self.seconds()
+    }
+
+    /// The VS score of tetr.io: attack and cleared garbage per 100 seconds
+    pub fn vs(&self) -> f64 {
+        (self.attack + self.garbage_cleared) as f64 * 100.0 / self.seconds()
+    }
+
+    /// Key presses per piece
+    pub fn kpp(&self) -> f64 {
+        self.keys as f64 / self.pieces.max(1) as f64
+    }
+
+    /// A name and a value for every statistic, like they are shown during the game
+    pub fn summary(&self) -> Vec<(String, String)> {
+        vec![
+            (String::from("pps"), format!("{:.2}", self.pps())),
+            (String::from("apm"), format!("{:.1}", self.apm())),
+            (String::from("vs"), format!("{:.1}", self.vs())),
+            (String::from("kpp"), format!("{:.2}", self.kpp())),
+            (String::from("lines"), self.lines.to_string()),
+            (String::from("max combo"), self.max_combo.to_string()),
+            (String::from("finesse"), self.finesse_faults.to_string()),
+        ]
+    }
+}
@@ -0,0 +1,119 @@
+use super::board::Board;
+
+/// What a single player game is played for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible in 2 minutes
+    Blitz,
+    /// Clear 150 lines while the pieces fall faster with every level
+    Marathon,
+    /// Play without any goal, the board can't top out
+    Zen,
+}
+
+/// The lines to clear in a sprint
+pub const SPRINT_LINES: u32 = 40;
+/// The length of a blitz in ticks
+pub const BLITZ_TICKS: u64 = 120 * crate::TICKS_PER_SECOND as u64;
+/// The lines to clear in a marathon
+pub const MARATHON_LINES: u32 = 150;
+
+impl Goal {
+    pub const ALL: [Goal; 4] = [Goal::Sprint, Goal::Blitz, Goal::Marathon, Goal::Zen];
+
+    /// The name of the goal as stored in files
+    pub fn name(self) -> &'static str {
+        match self {
+            Goal::Sprint => "sprint",
+            Goal::Blitz => "blitz",
+            Goal::Marathon => "marathon",
+            Goal::Zen => "zen",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.name() == name)
+    }
+
+    /// The name of the goal shown to the player
+    pub fn title(self) -> &'static str {
+        match self {
+            Goal::Sprint => "40 lines",
+            Goal::Blitz => "blitz",
+            Goal::Marathon => "marathon",
+            Goal::Zen => "zen",
+        }
+    }
+
+    /// Whether the board has reached the goal after `ticks` ticks
+    pub fn reached(self, board: &Board, ticks: u64) -> bool {
+        match self {
+            Goal::Sprint => board.score.lines >= SPRINT_LINES,
+            Goal::Blitz => ticks >= BLITZ_TICKS,
+            Goal::Marathon => board.score.lines >= MARATHON_LINES,
+            Goal::Zen => false,
+        }
+    }
+
+    /// The progress towards the goal shown under the board
+    pub fn progress(self, board: &Board, ticks: u64) -> String {
+        match self {
+            Goal::Sprint => format!(
+                "{}   {} lines left",
+                format_time(ticks),
+                SPRINT_LINES.saturating_sub(board.score.lines)
+            ),
+            Goal::Blitz => format!(
+                "{}   {} points",
+                format_time(BLITZ_TICKS.saturating_sub(ticks)),
+                board.score.score
+            ),
+            Goal::Marathon => format!(
+                "level {}   {} / {} lines",
+                board.score.level(),
+                board.score.lines,
+                MARATHON_LINES
+            ),
+            Goal::Zen => format!("{}   {} lines", format_time(ticks), board.score.lines),
+        }
+    }
+}
+
+/// Format a number of ticks as minutes, seconds and milliseconds
+pub fn format_time(ticks: u64) -> String {
+    let ms = ticks * 1000 / crate::TICKS_PER_SECOND as u64;
+    format!("{}:{:02}.{:03}", ms / 60000, ms / 1000 % 60, ms % 1000)
+}
+
+/// What is shown on the results screen after a game
+pub struct Results {
+    pub mode: super::GameMode,
+    /// The split screen series the game has been part of
+    pub series: Option<super::Series>,
+    /// Like "40 lines" or "game over"
+    pub title: String,
+    /// A name and a value for every line
+    pub stats: Vec<(String, String)>,
+}
+
+impl Results {
+    /// The screen which starts the next game of the series, or a new one of the same mode
+    pub fn again(&self) -> crate::Screen {
+        match &self.series {
+            Some(x) if x.winner().is_none() => crate::Screen::Rematch(x.clone()),
+            Some(x) => crate::Screen::Rematch(super::Series::new(x.wins.len(), x.best_of)),
+            None => self.mode.screen(),
+        }
+    }
+
+    /// What starting the next game is called
+    pub fn again_label(&self) -> &'static str {
+        match &self.series {
+            Some(x) if x.winner().is_none() => "next game",
+            Some(_) => "rematch",
+            None => "play again",
+        }
+    }
+}
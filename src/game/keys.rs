@@ -1,31 +1,117 @@
+use glutin::event::VirtualKeyCode;
 use std::collections::HashMap;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Key {
     Left,
     Right,
     RotateCW,
     RotateCCW,
+    Rotate180,
     SoftDrop,
     HardDrop,
     Swap,
+    /// Change how the garbage is sent to the opponents
+    Target,
+    /// Start the same game over
+    Restart,
+    Pause,
 }
 
+impl Key {
+    pub const ALL: [Key; 11] = [
+        Key::Left,
+        Key::Right,
+        Key::RotateCW,
+        Key::RotateCCW,
+        Key::Rotate180,
+        Key::SoftDrop,
+        Key::HardDrop,
+        Key::Swap,
+        Key::Target,
+        Key::Restart,
+        Key::Pause,
+    ];
+
+    /// The name of the key as stored in files
+    pub fn name(self) -> &'static str {
+        match self {
+            Key::Left => "left",
+            Key::Right => "right",
+            Key::RotateCW => "rotate_cw",
+            Key::RotateCCW => "rotate_ccw",
+            Key::Rotate180 => "rotate_180",
+            Key::SoftDrop => "soft_drop",
+            Key::HardDrop => "hard_drop",
+            Key::Swap => "swap",
+            Key::Target => "target",
+            Key::Restart => "restart",
+            Key::Pause => "pause",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.name() == name)
+    }
+}
+
+/// Generate the functions converting keyboard keys to and from the names stored in files, which
+/// are the names of the variants of [`VirtualKeyCode`]
+macro_rules! keycode_names {
+    ($($x:ident)*) => {
+        pub fn keycode_name(key: VirtualKeyCode) -> &'static str {
+            match key {
+                $(VirtualKeyCode::$x => stringify!($x),)*
+            }
+        }
+
+        pub fn keycode_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($x) => Some(VirtualKeyCode::$x),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+keycode_names! {
+    Key1 Key2 Key3 Key4 Key5 Key6 Key7 Key8 Key9 Key0 A B C D E F G H I J K L M N O P Q R S T U V W
+    X Y Z Escape F1 F2 F3 F4 F5 F6 F7 F8 F9 F10 F11 F12 F13 F14 F15 F16 F17 F18 F19 F20 F21 F22 F23
+    F24 Snapshot Scroll Pause Insert Home Delete End PageDown PageUp Left Up Right Down Back Return
+    Space Compose Caret Numlock Numpad0 Numpad1 Numpad2 Numpad3 Numpad4 Numpad5 Numpad6 Numpad7
+    Numpad8 Numpad9 NumpadAdd NumpadDivide NumpadDecimal NumpadComma NumpadEnter NumpadEquals
+    NumpadMultiply NumpadSubtract AbntC1 AbntC2 Apostrophe Apps Asterisk At Ax Backslash Calculator
+    Capital Colon Comma Convert Equals Grave Kana Kanji LAlt LBracket LControl LShift LWin Mail
+    MediaSelect MediaStop Minus Mute MyComputer NavigateForward NavigateBackward NextTrack
+    NoConvert OEM102 Period PlayPause Plus Power PrevTrack RAlt RBracket RControl RShift RWin
+    Semicolon Slash Sleep Stop Sysrq Tab Underline Unlabeled VolumeDown VolumeUp Wake WebBack
+    WebFavorites WebForward WebHome WebRefresh WebSearch WebStop Yen Copy Paste Cut
+}
+
+/// The keyboard keys of a player. Every key of the board can have any number of keyboard keys,
+/// but a keyboard key does only one thing.
+#[derive(Clone)]
 pub struct KeyBinds {
-    keys: HashMap<glutin::event::VirtualKeyCode, Key>,
+    keys: HashMap<VirtualKeyCode, Key>,
 }
 
 impl KeyBinds {
     pub fn single() -> Self {
         Self {
             keys: HashMap::from([
-                (glutin::event::VirtualKeyCode::Left, Key::Left),
-                (glutin::event::VirtualKeyCode::Right, Key::Right),
-                (glutin::event::VirtualKeyCode::Up, Key::RotateCW),
-                (glutin::event::VirtualKeyCode::LControl, Key::RotateCCW),
-                (glutin::event::VirtualKeyCode::Down, Key::SoftDrop),
-                (glutin::event::VirtualKeyCode::Space, Key::HardDrop),
-                (glutin::event::VirtualKeyCode::LShift, Key::Swap),
+                (VirtualKeyCode::Left, Key::Left),
+                (VirtualKeyCode::Right, Key::Right),
+                (VirtualKeyCode::Up, Key::RotateCW),
+                (VirtualKeyCode::X, Key::RotateCW),
+                (VirtualKeyCode::LControl, Key::RotateCCW),
+                (VirtualKeyCode::Z, Key::RotateCCW),
+                (VirtualKeyCode::A, Key::Rotate180),
+                (VirtualKeyCode::Down, Key::SoftDrop),
+                (VirtualKeyCode::Space, Key::HardDrop),
+                (VirtualKeyCode::LShift, Key::Swap),
+                (VirtualKeyCode::C, Key::Swap),
+                (VirtualKeyCode::R, Key::Restart),
+                (VirtualKeyCode::P, Key::Pause),
             ]),
         }
     }
@@ -33,13 +119,52 @@ impl KeyBinds {
     pub fn left() -> Self {
         Self {
             keys: HashMap::from([
-                (glutin::event::VirtualKeyCode::A, Key::Left),
-                (glutin::event::VirtualKeyCode::D, Key::Right),
-                (glutin::event::VirtualKeyCode::W, Key::RotateCW),
-                (glutin::event::VirtualKeyCode::LControl, Key::RotateCCW),
-                (glutin::event::VirtualKeyCode::S, Key::SoftDrop),
-                (glutin::event::VirtualKeyCode::Space, Key::HardDrop),
-                (glutin::event::VirtualKeyCode::LShift, Key::Swap),
+                (VirtualKeyCode::A, Key::Left),
+                (VirtualKeyCode::D, Key::Right),
+                (VirtualKeyCode::W, Key::RotateCW),
+                (VirtualKeyCode::LControl, Key::RotateCCW),
+                (VirtualKeyCode::E, Key::Rotate180),
+                (VirtualKeyCode::S, Key::SoftDrop),
+                (VirtualKeyCode::Space, Key::HardDrop),
+                (VirtualKeyCode::LShift, Key::Swap),
+                (VirtualKeyCode::Q, Key::Target),
+                (VirtualKeyCode::Tab, Key::Pause),
+            ]),
+        }
+    }
+
+    /// The keys in the middle of the keyboard, for the third player in split screen
+    pub fn center() -> Self {
+        Self {
+            keys: HashMap::from([
+                (VirtualKeyCode::J, Key::Left),
+                (VirtualKeyCode::L, Key::Right),
+                (VirtualKeyCode::I, Key::RotateCW),
+                (VirtualKeyCode::U, Key::RotateCCW),
+                (VirtualKeyCode::Y, Key::Rotate180),
+                (VirtualKeyCode::K, Key::SoftDrop),
+                (VirtualKeyCode::M, Key::HardDrop),
+                (VirtualKeyCode::N, Key::Swap),
+                (VirtualKeyCode::O, Key::Target),
+                (VirtualKeyCode::P, Key::Pause),
+            ]),
+        }
+    }
+
+    /// The arrow keys, for the fourth player in split screen
+    pub fn arrows() -> Self {
+        Self {
+            keys: HashMap::from([
+                (VirtualKeyCode::Left, Key::Left),
+                (VirtualKeyCode::Right, Key::Right),
+                (VirtualKeyCode::Up, Key::RotateCW),
+                (VirtualKeyCode::RControl, Key::RotateCCW),
+                (VirtualKeyCode::Slash, Key::Rotate180),
+                (VirtualKeyCode::Down, Key::SoftDrop),
+                (VirtualKeyCode::RShift, Key::HardDrop),
+                (VirtualKeyCode::Return, Key::Swap),
+                (VirtualKeyCode::RAlt, Key::Target),
+                (VirtualKeyCode::Back, Key::Pause),
             ]),
         }
     }
@@ -47,18 +172,111 @@ impl KeyBinds {
     pub fn right() -> Self {
         Self {
             keys: HashMap::from([
-                (glutin::event::VirtualKeyCode::Numpad1, Key::Left),
-                (glutin::event::VirtualKeyCode::Numpad3, Key::Right),
-                (glutin::event::VirtualKeyCode::Numpad5, Key::RotateCW),
-                (glutin::event::VirtualKeyCode::NumpadComma, Key::RotateCCW),
-                (glutin::event::VirtualKeyCode::Numpad2, Key::SoftDrop),
-                (glutin::event::VirtualKeyCode::NumpadEnter, Key::HardDrop),
-                (glutin::event::VirtualKeyCode::Numpad0, Key::Swap),
+                (VirtualKeyCode::Numpad1, Key::Left),
+                (VirtualKeyCode::Numpad3, Key::Right),
+                (VirtualKeyCode::Numpad5, Key::RotateCW),
+                (VirtualKeyCode::NumpadComma, Key::RotateCCW),
+                (VirtualKeyCode::Numpad8, Key::Rotate180),
+                (VirtualKeyCode::Numpad2, Key::SoftDrop),
+                (VirtualKeyCode::Numpad0, Key::HardDrop),
+                (VirtualKeyCode::NumpadEnter, Key::Swap),
+                (VirtualKeyCode::NumpadAdd, Key::Target),
+                (VirtualKeyCode::NumpadSubtract, Key::Pause),
             ]),
         }
     }
 
-    pub fn decode(&self, k: glutin::event::VirtualKeyCode) -> Option<Key> {
+    /// The default keys of every profile: the single player one, followed by the split screen
+    /// players
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::single(),
+            Self::left(),
+            Self::right(),
+            Self::center(),
+            Self::arrows(),
+        ]
+    }
+
+    pub fn decode(&self, k: VirtualKeyCode) -> Option<Key> {
         self.keys.get(&k).cloned()
     }
+
+    /// The keyboard keys bound to a key of the board, sorted by name
+    pub fn keycodes(&self, key: Key) -> Vec<VirtualKeyCode> {
+        let mut codes = self
+            .keys
+            .iter()
+            .filter(|(_, x)| **x == key)
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        // the map has no order, keep the result stable
+        codes.sort_by_key(|x| keycode_name(*x));
+        codes
+    }
+
+    /// Bind a keyboard key to a key of the board, in addition to the keys already bound to it.
+    /// The keyboard key stops doing whatever it did before.
+    pub fn bind(&mut self, key: Key, code: VirtualKeyCode) {
+        self.keys.insert(code, key);
+    }
+
+    pub fn unbind(&mut self, code: VirtualKeyCode) {
+        self.keys.remove(&code);
+    }
+
+    /// Remove all the keyboard keys bound to a key of the board
+    pub fn clear(&mut self, key: Key) {
+        self.keys.retain(|_, x| *x != key);
+    }
+
+    /// The keyboard keys which are bound in both keybinds
+    pub fn conflicts(&self, other: &Self) -> Vec<VirtualKeyCode> {
+        self.keys
+            .keys()
+            .filter(|x| other.keys.contains_key(x))
+            .copied()
+            .collect()
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut data = json::JsonValue::new_object();
+        for key in Key::ALL {
+            data[key.name()] = self
+                .keycodes(key)
+                .into_iter()
+                .map(keycode_name)
+                .collect::<Vec<&str>>()
+                .into();
+        }
+        data
+    }
+
+    /// Read the keys written by [`KeyBinds::to_json`], falling back to `default` for keys which
+    /// aren't in the file. A single key may be given as a string instead of a list.
+    pub fn from_json(data: &json::JsonValue, default: &Self) -> Self {
+        let mut keybinds = default.clone();
+        for (name, codes) in data.entries() {
+            let key = match Key::from_name(name) {
+                Some(x) => x,
+                None => {
+                    eprintln!("Unknown action {} in the keymap", name);
+                    continue;
+                }
+            };
+            keybinds.clear(key);
+            let codes = if codes.is_string() {
+                vec![codes]
+            } else {
+                codes.members().collect()
+            };
+            for code in codes.into_iter().filter_map(|x| x.as_str()) {
+                match keycode_from_name(code) {
+                    Some(code) => keybinds.bind(key, code),
+                    None => eprintln!("Unknown key {} bound to {}", code, name),
+                }
+            }
+        }
+        keybinds
+    }
 }
@@ -1,41 +1,143 @@
 use glam::Mat4;
 use glam::Vec3;
+use glam::Vec4;
 use rand::prelude::*;
 use std::{cell::RefCell, rc::Rc};
 
 mod background;
 mod block;
 mod board;
+mod goal;
 mod keys;
+mod online;
+mod pause;
+mod player;
+mod replay;
+mod rotation;
+mod series;
+mod targeting;
 mod tetromino;
 mod util;
 
+pub use background::wallpapers;
 use block::Block;
 use board::Board;
+pub use board::{AllSpin, Handling, RandomizerKind, Rules};
+pub use goal::{format_time, Goal, Results};
+pub use keys::{keycode_name, Key, KeyBinds};
+use player::Player;
+pub use replay::Replay;
+pub use rotation::RotationSystem;
+pub use series::{Series, MAX_BEST_OF};
 use tetromino::Tetromino;
 use util::BlockPos;
 
-#[derive(Clone, Copy)]
-pub enum KeyTiming {
-    /// The key always applies (like soft drop) but doesn't repeat
-    None,
-    Delayed(std::time::Instant),
-    Repeat(std::time::Instant),
-    /// The key applies on key press, and it's been applied
-    Single,
-}
-
 pub struct Game {
-    boards: Vec<Rc<RefCell<Board>>>,
+    players: Vec<Player>,
     renderer: board::Renderer,
-    keys_pressed: std::collections::HashMap<glutin::event::VirtualKeyCode, KeyTiming>,
     exiting: bool,
     background: background::Background,
+
+    seed: u64,
+    /// The number of ticks the game has been running for
+    tick: u64,
+    /// The inputs recorded so far, or the replay being watched
+    replay: Replay,
+    /// Some when watching a replay
+    playback: Option<replay::Playback>,
+    /// Some when playing against someone over the network
+    online: Option<online::Online>,
+    rotation: Rc<RotationSystem>,
+    rules: Rc<Rules>,
+    /// Used by the targeting strategies, seeded so that replays pick the same targets
+    rng: SmallRng,
+    /// The last player standing, once everyone else has topped out
+    winner: Option<usize>,
+    /// Some while the game is paused, the boards don't run and the pause menu is shown
+    pause: Option<pause::Pause>,
+    /// The split screen players who have pressed the pause key, the game only pauses once all
+    /// the players still standing have
+    pause_votes: Vec<bool>,
+    /// The ticks left before the boards start, or run again after resuming a split screen game
+    countdown: u64,
+    /// The split screen series the game is part of
+    series: Option<Series>,
+    /// Set by the restart key, the same mode starts again on the next screen change
+    restarting: bool,
+    /// When the goal of a single player game has been reached, the boards stop running
+    finished: Option<std::time::Instant>,
+    /// Given to the gamepads assigned to the boards
+    gamepad_binds: crate::gamepad::GamepadBinds,
+    /// The names of the players of the boards, for the records
+    names: Vec<String>,
+
+    tr: Rc<crate::text::TextRenderer>,
+    font: crate::text::Font,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GameMode {
-    Single,
-    Double,
+    /// A single player playing for a goal
+    Single(Goal),
+    /// The given number of players sharing the keyboard, from 2 to [`MAX_PLAYERS`]
+    Split(usize),
+    /// Against another player over the network
+    Online,
+}
+
+/// The most players that fit on a single keyboard
+pub const MAX_PLAYERS: usize = 4;
+
+impl GameMode {
+    /// The name of the mode as stored in files
+    pub fn name(self) -> String {
+        match self {
+            GameMode::Single(goal) => String::from(goal.name()),
+            GameMode::Split(2) => String::from("double"),
+            GameMode::Split(n) => format!("split{}", n),
+            GameMode::Online => String::from("online"),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "double" => Some(GameMode::Split(2)),
+            "online" => Some(GameMode::Online),
+            _ => match name.strip_prefix("split") {
+                Some(n) => match n.parse() {
+                    Ok(n) if (2..=MAX_PLAYERS).contains(&n) => Some(GameMode::Split(n)),
+                    _ => None,
+                },
+                None => Goal::from_name(name).map(GameMode::Single),
+            },
+        }
+    }
+
+    /// The number of boards in the game
+    pub fn players(self) -> usize {
+        match self {
+            GameMode::Single(_) => 1,
+            GameMode::Split(n) => n,
+            GameMode::Online => 2,
+        }
+    }
+
+    /// The settings profile of every board played on this computer
+    pub fn profiles(self) -> Vec<usize> {
+        match self {
+            GameMode::Single(_) | GameMode::Online => vec![0],
+            GameMode::Split(n) => (1..=n).collect(),
+        }
+    }
+
+    /// The screen which starts a new game of this mode
+    pub fn screen(self) -> crate::Screen {
+        match self {
+            GameMode::Single(goal) => crate::Screen::SingleGame(goal),
+            GameMode::Split(n) => crate::Screen::SplitGame(n),
+            GameMode::Online => crate::Screen::OnlineGame,
+        }
+    }
 }
 
 impl Game {
@@ -46,42 +148,637 @@ impl Game {
         mode: GameMode,
         audio: Rc<RefCell<kira::manager::AudioManager>>,
     ) -> Self {
-        let rng = SmallRng::from_entropy();
-        let boards = match mode {
-            GameMode::Double => {
-                let left = Rc::new(RefCell::new(Board::new(
-                    keys::KeyBinds::left(),
-                    rng.clone(),
-                    audio.clone(),
-                    roman,
-                )));
-                let right = Rc::new(RefCell::new(Board::new(
-                    keys::KeyBinds::right(),
-                    rng,
+        // both boards get the same seed, so that they get the same pieces
+        let seed = rand::random::<u64>();
+        let settings = crate::settings::Settings::load();
+        let mut handling = mode
+            .profiles()
+            .into_iter()
+            .map(|x| settings.handling[x])
+            .collect::<Vec<Handling>>();
+        // the opponent's board online is simulated on the other computer
+        handling.resize(mode.players(), Handling::default());
+        // both players online have to agree on the rules, so they use the default ones
+        let (rotation, mut rules) = match mode {
+            GameMode::Online => (String::from("srs+"), Rules::default()),
+            _ => (settings.rotation.clone(), settings.rules.clone()),
+        };
+        // the randomizer can be chosen for every goal and for split screen
+        let randomizer = match mode {
+            GameMode::Single(goal) => settings.single_randomizers[goal as usize],
+            GameMode::Split(_) => settings.split_randomizer,
+            GameMode::Online => None,
+        };
+        if let Some(x) = randomizer {
+            rules.randomizer = x;
+        }
+        let mut game = Self::with_replay(
+            gh,
+            roman,
+            tr,
+            Replay::new(seed, mode, rotation, rules, handling),
+            audio,
+            false,
+            &settings,
+        );
+        game.countdown = COUNTDOWN;
+        if let GameMode::Split(n) = mode {
+            game.series = Some(Series::new(n, settings.best_of));
+        }
+        game
+    }
+
+    /// Play the next game of a split screen series
+    pub fn rematch(
+        gh: &mut crate::graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<crate::text::TextRenderer>,
+        series: Series,
+        audio: Rc<RefCell<kira::manager::AudioManager>>,
+    ) -> Self {
+        let mut game = Self::new(gh, roman, tr, GameMode::Split(series.wins.len()), audio);
+        game.series = Some(series);
+        game
+    }
+
+    /// Play against someone else through the relay server
+    pub fn online(
+        gh: &mut crate::graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<crate::text::TextRenderer>,
+        audio: Rc<RefCell<kira::manager::AudioManager>>,
+    ) -> std::io::Result<Self> {
+        let online = online::Online::connect()?;
+        // the seed is decided by the relay once the match starts
+        let mut game = Self::new(gh, roman, tr, GameMode::Online, audio);
+        game.online = Some(online);
+        // the relay starts the match
+        game.countdown = 0;
+        Ok(game)
+    }
+
+    /// Watch a recorded game
+    pub fn from_replay(
+        gh: &mut crate::graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<crate::text::TextRenderer>,
+        replay: Replay,
+        audio: Rc<RefCell<kira::manager::AudioManager>>,
+    ) -> Self {
+        Self::with_replay(
+            gh,
+            roman,
+            tr,
+            replay,
+            audio,
+            true,
+            &crate::settings::Settings::load(),
+        )
+    }
+
+    fn with_replay(
+        gh: &mut crate::graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<crate::text::TextRenderer>,
+        replay: Replay,
+        audio: Rc<RefCell<kira::manager::AudioManager>>,
+        playback: bool,
+        settings: &crate::settings::Settings,
+    ) -> Self {
+        let seed = replay.seed;
+        let mode = replay.mode;
+        let rotation = Rc::new(
+            RotationSystem::by_name(&replay.rotation).unwrap_or_else(|e| {
+                eprintln!("Unable to load rotation system {}: {}", replay.rotation, e);
+                RotationSystem::srs_plus()
+            }),
+        );
+        let rules = Rc::new(replay.rules.clone());
+        let keybinds = mode.profiles().into_iter().map(|x| &settings.keybinds[x]);
+        let mut players = keybinds
+            .enumerate()
+            .map(|(i, keybinds)| {
+                Player::new(
+                    Board::new(seed, replay.handling[i], rotation.clone(), rules.clone()),
+                    keybinds.clone(),
                     audio.clone(),
                     roman,
-                )));
-                {
-                    left.borrow_mut().victim = Some(right.clone());
-                    right.borrow_mut().victim = Some(left.clone());
-                }
-                vec![left, right]
-            }
-            GameMode::Single => vec![Rc::new(RefCell::new(Board::new(
-                keys::KeyBinds::single(),
-                rng,
+                )
+            })
+            .collect::<Vec<Player>>();
+        if mode == GameMode::Online {
+            let mut remote = Player::new(
+                Board::new(seed, replay.handling[1], rotation.clone(), rules.clone()),
+                settings.keybinds[0].clone(),
                 audio.clone(),
                 roman,
-            )))],
-        };
+            );
+            remote.remote = true;
+            players.push(remote);
+        }
+        for player in &mut players {
+            // with more than two players the game goes on after someone tops out
+            player.effects.grey_out = mode.players() > 2;
+            player.effects.show_particles = settings.particles;
+            player.effects.motion = settings.board_motion;
+            player.effects.width = rules.width;
+        }
+        if let Err(e) = audio
+            .borrow()
+            .main_track()
+            .set_volume(settings.volume, kira::tween::Tween::default())
+        {
+            eprintln!("Unable to set the volume: {}", e);
+        }
+
         Self {
-            renderer: board::Renderer::new(gh, roman, tr),
-            boards,
-            keys_pressed: std::collections::HashMap::new(),
+            renderer: board::Renderer::new(gh, roman, tr.clone(), &rules),
+            playback: if playback {
+                Some(replay::Playback::new(players.len()))
+            } else {
+                None
+            },
+            players,
+            online: None,
+            rng: SmallRng::seed_from_u64(seed),
+            winner: None,
+            rotation,
+            rules,
+            pause: None,
+            pause_votes: vec![false; mode.players()],
+            countdown: 0,
+            series: None,
+            restarting: false,
+            finished: None,
+            gamepad_binds: settings.gamepad.clone(),
+            names: mode
+                .profiles()
+                .into_iter()
+                .map(|x| settings.names[x].clone())
+                .collect(),
             exiting: false,
-            background: background::Background::new(gh, roman),
+            background: background::Background::new(gh, roman, &settings.wallpaper),
+            seed,
+            tick: 0,
+            replay,
+            font: crate::text::Font::new(&tr, roman.get_binary("teko-light.ttf"), 100).unwrap(),
+            tr,
+        }
+    }
+
+    /// Run the game for a single tick
+    ///
+    /// * `silent` - don't play any sounds, used when seeking in a replay
+    fn step(&mut self, silent: bool) {
+        if self.finished.is_some() {
+            return;
+        }
+        self.apply_goal();
+
+        // feed the boards the inputs they got in the replay
+        if let Some(playback) = &mut self.playback {
+            let mut targeting = Vec::new();
+            for (i, player) in self.players.iter_mut().enumerate() {
+                let inputs = &self.replay.inputs[i];
+                while let Some(x) = inputs.get(playback.next_input[i]) {
+                    if x.tick > self.tick {
+                        break;
+                    }
+                    if x.pressed {
+                        player.board.press(x.key);
+                        if x.key == keys::Key::Target {
+                            targeting.push(i);
+                        }
+                    } else {
+                        player.board.release(x.key);
+                    }
+                    playback.next_input[i] += 1;
+                }
+            }
+            for i in targeting {
+                self.change_targeting(i);
+            }
+        }
+
+        // keys held during the countdown
+        if self.tick == 0 {
+            for player in &mut self.players {
+                player.board.charge_das();
+            }
+        }
+
+        self.tick += 1;
+        let mut sent = Vec::new();
+        for i in 0..self.players.len() {
+            sent.push(self.players[i].update(silent));
+        }
+        if self.online.is_some() {
+            self.send_online(sent[0]);
+            return;
+        }
+        if let Some(goal) = self.goal() {
+            if goal.reached(&self.players[0].board, self.tick) {
+                self.finished = Some(std::time::Instant::now());
+                let text = match goal {
+                    Goal::Blitz => "TIME'S UP",
+                    _ => "FINISH",
+                };
+                self.players[0]
+                    .effects
+                    .handle(&board::Event::Announced(String::from(text)));
+            }
+        }
+        for (i, sent) in sent.into_iter().enumerate() {
+            if sent == 0 {
+                continue;
+            }
+            let candidates = self.candidates();
+            let player = &mut self.players[i];
+            player.target = player.targeting.choose(i, &candidates, &mut self.rng);
+            if let Some(victim) = player.target {
+                self.players[victim].board.receive_garbage(sent);
+            }
+        }
+        self.announce_winner();
+    }
+
+    /// The goal of a single player game
+    fn goal(&self) -> Option<Goal> {
+        match self.replay.mode {
+            GameMode::Single(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Make the board play by the goal of a single player game
+    fn apply_goal(&mut self) {
+        let goal = self.goal();
+        let board = &mut self.players[0].board;
+        match goal {
+            Some(Goal::Marathon) => board.set_gravity(board.score.gravity()),
+            Some(Goal::Zen) => board.top_out = false,
+            _ => (),
+        }
+    }
+
+    /// Add the games of all the players to the records, and the match to the split screen ones
+    ///
+    /// # Return value
+    ///
+    /// Returns the records and whether a player has got a new personal best
+    fn record(&self) -> (crate::records::Records, bool) {
+        let mut records = crate::records::Records::load();
+        let date = crate::timestamp();
+        let mut best = false;
+        for (player, name) in self.players.iter().zip(&self.names) {
+            best |= records.add_game(crate::records::GameRecord {
+                player: name.clone(),
+                mode: self.replay.mode,
+                date: date.clone(),
+                ticks: self.tick,
+                finished: self.finished.is_some(),
+                score: player.board.score.score,
+                lines: player.stats.lines,
+                pps: player.stats.pps(),
+                apm: player.stats.apm(),
+                vs: player.stats.vs(),
+                kpp: player.stats.kpp(),
+                max_combo: player.stats.max_combo,
+                finesse_faults: player.stats.finesse_faults,
+            });
+        }
+        if let (GameMode::Split(_), Some(winner)) = (self.replay.mode, self.winner) {
+            records.add_match(&self.names, &self.names[winner]);
+        }
+        if let Err(e) = records.save() {
+            eprintln!("Unable to save records: {}", e);
+        }
+        (records, best)
+    }
+
+    /// The screen which starts the same game again, in the same series
+    fn again(&self) -> crate::Screen {
+        match &self.series {
+            Some(x) => crate::Screen::Rematch(x.clone()),
+            None => self.replay.mode.screen(),
+        }
+    }
+
+    /// What is shown once a split screen game is over, the series has already been counted
+    fn split_results(&self) -> Results {
+        let series = self.series.clone().filter(|x| x.best_of > 1);
+        let mut stats = vec![(String::from("players"), self.names.join("   "))];
+        if let Some(x) = &series {
+            stats.push((String::from("series"), x.score()));
+        }
+        let summaries = self
+            .players
+            .iter()
+            .map(|x| x.stats.summary())
+            .collect::<Vec<_>>();
+        for (i, (name, _)) in summaries[0].iter().enumerate() {
+            stats.push((
+                name.clone(),
+                summaries
+                    .iter()
+                    .map(|x| x[i].1.clone())
+                    .collect::<Vec<String>>()
+                    .join("   "),
+            ));
+        }
+        Results {
+            mode: self.replay.mode,
+            series: self.series.clone(),
+            title: match self.winner {
+                Some(x) if series.as_ref().is_some_and(|s| s.winner() == Some(x)) => {
+                    format!("{} wins the series", self.names[x])
+                }
+                Some(x) => format!("{} wins", self.names[x]),
+                None => String::from("draw"),
+            },
+            stats,
+        }
+    }
+
+    /// What is shown once a single player game is over
+    fn results(&self, goal: Goal, records: &crate::records::Records, best: bool) -> Results {
+        let board = &self.players[0].board;
+        let mut stats = vec![
+            (String::from("time"), goal::format_time(self.tick)),
+            (String::from("score"), board.score.score.to_string()),
+        ];
+        if let Some(x) = records.best(self.replay.mode, &self.names[0]) {
+            stats.push((String::from("personal best"), x.result()));
+        }
+        stats.extend(self.players[0].stats.summary());
+        Results {
+            mode: self.replay.mode,
+            series: None,
+            title: if best {
+                format!("{}  new personal best", goal.title())
+            } else if board.death_tick.is_some() {
+                String::from("game over")
+            } else {
+                String::from(goal.title())
+            },
+            stats,
         }
     }
+
+    /// React to a key of player `i` pressed or released on the keyboard or on a gamepad, which
+    /// has been passed to the board by [`Player::key`]
+    fn key_changed(&mut self, i: usize, key: keys::Key, pressed: bool) {
+        match key {
+            // the opponent online wouldn't stop
+            keys::Key::Pause if self.online.is_none() => self.pause_pressed(i),
+            keys::Key::Restart if self.online.is_none() => self.restarting = true,
+            keys::Key::Target if pressed => self.change_targeting(i),
+            _ => (),
+        }
+        // the replay only needs the keys of the boards
+        if !matches!(key, keys::Key::Pause | keys::Key::Restart) {
+            self.replay.inputs[i].push(replay::KeyEvent {
+                tick: self.tick,
+                key,
+                pressed,
+            });
+        }
+    }
+
+    /// Pause or resume the game after player `i` has pressed the pause key. In split screen
+    /// every player still standing has to press it before the game pauses.
+    fn pause_pressed(&mut self, i: usize) {
+        if self.pause.is_some() {
+            self.resume();
+            return;
+        }
+        if let GameMode::Split(_) = self.replay.mode {
+            self.pause_votes[i] = !self.pause_votes[i];
+            let agreed = self
+                .players
+                .iter()
+                .zip(&self.pause_votes)
+                .all(|(player, vote)| *vote || player.board.death_tick.is_some());
+            if !agreed {
+                return;
+            }
+        }
+        self.pause_now();
+    }
+
+    fn pause_now(&mut self) {
+        self.pause = Some(pause::Pause::new());
+        self.pause_votes.iter_mut().for_each(|x| *x = false);
+        for player in &mut self.players {
+            player.frozen = true;
+        }
+    }
+
+    /// Let the boards run again, after a countdown in split screen
+    fn resume(&mut self) {
+        let pause = match self.pause.take() {
+            Some(x) => x,
+            None => return,
+        };
+        // the time spent paused doesn't count for the effects and the end of the game
+        let paused = pause.since.elapsed();
+        for player in &mut self.players {
+            player.frozen = false;
+            player.effects.delay(paused);
+        }
+        if let Some(x) = &mut self.finished {
+            *x += paused;
+        }
+        if let GameMode::Split(_) = self.replay.mode {
+            self.countdown = COUNTDOWN;
+        }
+    }
+
+    /// React to a key pressed in the pause menu
+    fn pause_menu(&mut self, x: glutin::event::VirtualKeyCode) {
+        let choice = match &mut self.pause {
+            Some(pause) => pause.press(x),
+            None => return,
+        };
+        match choice {
+            Some(pause::Choice::Resume) => self.resume(),
+            Some(pause::Choice::Restart) => self.restarting = true,
+            Some(pause::Choice::Quit) => self.exiting = true,
+            None => (),
+        }
+    }
+
+    /// Draw the pause menu, or the countdown after resuming
+    fn draw_pause(&mut self, gh: &mut crate::graphics::GraphicsHandle, aspect: f32) {
+        let mat = Mat4::from_scale(Vec3::new(1.0 / aspect, 1.0, 1.0));
+        let mut lines = Vec::new();
+        if let Some(pause) = &self.pause {
+            lines.push((String::from("PAUSED"), 1.0));
+            for (i, x) in pause::Choice::ALL.iter().enumerate() {
+                lines.push((
+                    String::from(x.label()),
+                    if i == pause.selected { 1.0 } else { 0.4 },
+                ));
+            }
+        } else if self.countdown > 0 {
+            let seconds = self.countdown.div_ceil(crate::TICKS_PER_SECOND as u64);
+            lines.push((seconds.to_string(), 1.0));
+        }
+        for (i, (text, alpha)) in lines.iter().enumerate() {
+            let width = self.tr.get_width(gh, &mut self.font, text);
+            self.tr.draw(
+                gh,
+                &mut self.font,
+                mat * Mat4::from_translation(Vec3::new(
+                    -width * 0.0015 * 0.5,
+                    0.2 - i as f32 * 0.2,
+                    0.0,
+                )) * Mat4::from_scale(Vec3::new(0.0015, 0.0015, 1.0)),
+                Vec4::new(1.0, 1.0, 1.0, *alpha),
+                text,
+            );
+        }
+    }
+
+    /// What the targeting strategies need to know about the players
+    fn candidates(&self) -> Vec<targeting::Candidate> {
+        self.players
+            .iter()
+            .map(|x| targeting::Candidate {
+                alive: x.board.death_tick.is_none(),
+                target: x.target,
+                danger: x.board.stack_height()
+                    + x.board.lines_received.iter().map(|x| x.lines).sum::<u32>(),
+            })
+            .collect()
+    }
+
+    /// Switch the player to the next targeting strategy
+    fn change_targeting(&mut self, i: usize) {
+        let candidates = self.candidates();
+        let player = &mut self.players[i];
+        player.targeting = player.targeting.next(i, &candidates);
+        if let targeting::Targeting::Manual(x) = player.targeting {
+            player.target = Some(x);
+        }
+    }
+
+    /// The players whose boards haven't topped out yet
+    fn alive(&self) -> usize {
+        self.players
+            .iter()
+            .filter(|x| x.board.death_tick.is_none())
+            .count()
+    }
+
+    /// Congratulate the last player standing once everyone else has topped out
+    fn announce_winner(&mut self) {
+        if self.players.len() < 2 || self.winner.is_some() || self.alive() != 1 {
+            return;
+        }
+        self.winner = self
+            .players
+            .iter()
+            .position(|x| x.board.death_tick.is_none());
+        if let Some(x) = self.winner {
+            self.players[x]
+                .effects
+                .handle(&board::Event::Announced(String::from("WINNER")));
+        }
+    }
+
+    /// Jump to the given tick of the replay being watched
+    fn seek(&mut self, tick: u64) {
+        let tick = tick.min(self.replay.ticks);
+        if tick < self.tick {
+            // the boards can't go back in time, so simulate everything from the start again
+            for (player, handling) in self.players.iter_mut().zip(&self.replay.handling) {
+                player.reset(Board::new(
+                    self.seed,
+                    *handling,
+                    self.rotation.clone(),
+                    self.rules.clone(),
+                ));
+            }
+            if let Some(playback) = &mut self.playback {
+                playback.next_input.iter_mut().for_each(|x| *x = 0);
+            }
+            self.rng = SmallRng::seed_from_u64(self.seed);
+            self.winner = None;
+            self.finished = None;
+            self.tick = 0;
+        }
+        while self.tick < tick {
+            self.step(true);
+        }
+    }
+
+    /// Draw the playback controls when watching a replay
+    fn draw_playback(&mut self, gh: &mut crate::graphics::GraphicsHandle, aspect: f32) {
+        let playback = match &self.playback {
+            Some(x) => x,
+            None => return,
+        };
+        let seconds = |ticks: u64| {
+            let s = ticks / crate::TICKS_PER_SECOND as u64;
+            format!("{}:{:02}", s / 60, s % 60)
+        };
+        let text = format!(
+            "REPLAY {}   {} / {}   {}",
+            if playback.paused {
+                String::from("PAUSED")
+            } else {
+                format!("{}×", playback.speed as f32 / 2.0)
+            },
+            seconds(self.tick),
+            seconds(self.replay.ticks),
+            "SPACE pause   ↑↓ speed   ←→ seek",
+        );
+        self.draw_status(gh, aspect, &text);
+    }
+
+    /// Draw a line of text in the top left corner
+    fn draw_status(&mut self, gh: &mut crate::graphics::GraphicsHandle, aspect: f32, text: &str) {
+        self.tr.draw(
+            gh,
+            &mut self.font,
+            Mat4::from_scale(Vec3::new(1.0 / aspect, 1.0, 1.0))
+                * Mat4::from_translation(Vec3::new(-aspect + 0.05, 0.9, 0.0))
+                * Mat4::from_scale(Vec3::new(0.0006, 0.0006, 1.0)),
+            Vec4::new(1.0, 1.0, 1.0, 0.8),
+            text,
+        );
+    }
+}
+
+/// The length of the countdown before a game starts, in ticks
+const COUNTDOWN: u64 = 3 * crate::TICKS_PER_SECOND as u64;
+
+/// The space taken by a board with its hold slot, queue and label, in the units of [`Game::draw`]
+fn board_size(rules: &Rules) -> (f32, f32) {
+    (
+        (rules.width + 12) as f32 * 0.1,
+        (rules.height + 4) as f32 * 0.1,
+    )
+}
+
+/// Arrange the boards in the grid which lets them be drawn the largest
+///
+/// # Return value
+///
+/// Returns the number of columns and the scale of the boards
+fn layout(count: usize, aspect: f32, size: (f32, f32)) -> (usize, f32) {
+    let mut best = (count, 0.0);
+    for rows in 1..=count {
+        let columns = count.div_ceil(rows);
+        let scale = (2.0 * aspect / (columns as f32 * size.0))
+            .min(2.0 / (rows as f32 * size.1))
+            .min(0.75);
+        if scale > best.1 {
+            best = (columns, scale);
+        }
+    }
+    best
 }
 
 impl crate::Playable for Game {
@@ -92,64 +789,259 @@ impl crate::Playable for Game {
         screen_height: i32,
     ) {
         let aspect = screen_width as f32 / screen_height as f32;
+        let count = self.players.len();
+        let size = board_size(&self.rules);
+        let (columns, scale) = layout(count, aspect, size);
+        let rows = count.div_ceil(columns);
         let mat = Mat4::from_scale(Vec3::new(1.0 / aspect, 1.0, 1.0))
-            * Mat4::from_scale(Vec3::new(0.75, 0.75, 0.75));
+            * Mat4::from_scale(Vec3::new(scale, scale, scale));
 
         self.background.draw(gh, screen_width, screen_height);
 
-        for i in 0..self.boards.len() {
+        for (i, player) in self.players.iter().enumerate() {
+            let (column, row) = (i % columns, i / columns);
             let mat =
                 mat * Mat4::from_translation(Vec3::new(
-                    0.6 - 2.2 * 0.5 * self.boards.len() as f32 + 2.2 * i as f32,
-                    -1.0,
+                    (column as f32 - (columns - 1) as f32 * 0.5) * size.0
+                        - self.rules.width as f32 * 0.05,
+                    ((rows - 1) as f32 * 0.5 - row as f32) * size.1
+                        - self.rules.height as f32 * 0.05,
                     0.0,
                 )) * Mat4::from_scale(Vec3::new(0.1, 0.1, 0.1));
 
-            self.renderer.draw(gh, &self.boards[i].borrow(), mat);
+            self.renderer
+                .draw(gh, &player.board, &player.effects, &player.stats, mat);
+
+            // with two players there is only one possible target
+            if count > 2 {
+                let dead = player.board.death_tick.is_some();
+                self.tr.draw(
+                    gh,
+                    &mut self.font,
+                    mat * Mat4::from_translation(Vec3::new(0.0, -2.6, 0.0))
+                        * Mat4::from_scale(Vec3::new(0.015, 0.015, 0.015)),
+                    Vec4::new(1.0, 1.0, 1.0, if dead { 0.3 } else { 0.8 }),
+                    &if dead {
+                        format!("P{}", i + 1)
+                    } else {
+                        format!("P{} → {}", i + 1, player.targeting.label())
+                    },
+                );
+            } else if let Some(goal) = self.goal() {
+                self.tr.draw(
+                    gh,
+                    &mut self.font,
+                    mat * Mat4::from_translation(Vec3::new(0.0, -2.6, 0.0))
+                        * Mat4::from_scale(Vec3::new(0.015, 0.015, 0.015)),
+                    Vec4::new(1.0, 1.0, 1.0, 0.8),
+                    &goal.progress(&player.board, self.tick),
+                );
+            }
         }
 
-        // self.board.draw(gl, mat);
+        self.draw_playback(gh, aspect);
+        self.draw_pause(gh, aspect);
+        if let Some(online) = &self.online {
+            let text = online.status();
+            self.draw_status(gh, aspect, &text);
+        } else if self.pause.is_none() && self.pause_votes.contains(&true) {
+            let text = self
+                .pause_votes
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| **x)
+                .map(|(i, _)| format!("P{}", i + 1))
+                .collect::<Vec<String>>()
+                .join(", ");
+            self.draw_status(gh, aspect, &format!("{} WANT TO PAUSE", text));
+        }
     }
 
     fn update(&mut self) {
-        for i in &mut self.boards {
-            i.borrow_mut().update(&mut self.keys_pressed);
+        if !self.update_online() {
+            // still let the effects play out
+            for player in &mut self.players {
+                player.effects.update();
+            }
+            return;
+        }
+
+        if self.pause.is_some() {
+            return;
+        }
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return;
+        }
+        let steps = match &mut self.playback {
+            None => 1,
+            Some(playback) => {
+                if playback.paused || self.tick >= self.replay.ticks {
+                    0
+                } else {
+                    playback.progress += playback.speed;
+                    let steps = playback.progress / 2;
+                    playback.progress %= 2;
+                    steps
+                }
+            }
+        };
+        for _ in 0..steps {
+            self.step(false);
         }
     }
 
     fn input(&mut self, input: glutin::event::KeyboardInput) {
-        match input.state {
-            glutin::event::ElementState::Pressed => {
-                if let Some(x) = input.virtual_keycode {
-                    if let glutin::event::VirtualKeyCode::Escape = x {
-                        self.exiting = true
-                    }
-                    if !self.keys_pressed.contains_key(&x) {
-                        self.keys_pressed.insert(x, KeyTiming::None);
+        use glutin::event::{ElementState, VirtualKeyCode};
+
+        let x = match input.virtual_keycode {
+            Some(x) => x,
+            None => return,
+        };
+        let pressed = matches!(input.state, ElementState::Pressed);
+        if x == VirtualKeyCode::Escape && pressed && self.pause.is_none() {
+            // the game can't be paused online, and a replay goes back to the list
+            if self.online.is_some() || self.playback.is_some() {
+                self.exiting = true;
+                return;
+            }
+            // in split screen both players have to vote with their own pause keys
+            if !matches!(self.replay.mode, GameMode::Split(_)) {
+                self.pause_now();
+                return;
+            }
+        }
+
+        if let Some(playback) = &mut self.playback {
+            if let ElementState::Pressed = input.state {
+                let five_seconds = 5 * crate::TICKS_PER_SECOND as u64;
+                match x {
+                    VirtualKeyCode::Space => playback.paused = !playback.paused,
+                    VirtualKeyCode::Up => playback.faster(),
+                    VirtualKeyCode::Down => playback.slower(),
+                    VirtualKeyCode::Left => self.seek(self.tick.saturating_sub(five_seconds)),
+                    VirtualKeyCode::Right => self.seek(self.tick + five_seconds),
+                    _ => (),
+                }
+            }
+            return;
+        }
+
+        for i in 0..self.players.len() {
+            if let Some(key) = self.players[i].input(x, input.state) {
+                self.key_changed(i, key, pressed);
+            }
+        }
+        // after the boards, which ignore the key while frozen
+        if pressed {
+            self.pause_menu(x);
+        }
+    }
+
+    fn gamepad(&mut self, event: crate::gamepad::Event) {
+        use crate::gamepad::Event;
+
+        if self.playback.is_some() {
+            return;
+        }
+        match &event {
+            Event::Connected { device, .. } => {
+                // the first board without a gamepad gets it
+                let free = self
+                    .players
+                    .iter_mut()
+                    .find(|x| !x.remote && x.gamepad.is_none());
+                if let Some(player) = free {
+                    player.gamepad = Some(crate::gamepad::Controller::new(
+                        *device,
+                        self.gamepad_binds.clone(),
+                    ));
+                }
+            }
+            Event::Disconnected(device) => {
+                for i in 0..self.players.len() {
+                    let player = &mut self.players[i];
+                    let released = match &mut player.gamepad {
+                        Some(x) if x.device == *device => x.release_all(),
+                        _ => continue,
+                    };
+                    for (key, pressed) in released {
+                        if let Some(key) =
+                            self.players[i].key(key, pressed, player::Source::Gamepad)
+                        {
+                            self.key_changed(i, key, pressed);
+                        }
                     }
+                    self.players[i].gamepad = None;
+                }
+            }
+            Event::Button { pressed: true, .. } if self.pause.is_some() => {
+                if let Some(x) = event.menu_key() {
+                    self.pause_menu(x);
                 }
             }
-            glutin::event::ElementState::Released => {
-                if let Some(x) = input.virtual_keycode {
-                    self.keys_pressed.remove(&x);
+            Event::Button { .. } | Event::Axis { .. } => {
+                for i in 0..self.players.len() {
+                    for (key, pressed) in self.players[i].gamepad_input(&event) {
+                        self.key_changed(i, key, pressed);
+                    }
                 }
             }
         }
     }
 
     fn next_screen(&mut self) -> Option<crate::Screen> {
-        if self.exiting {
+        // the game is over when a single player tops out, or when a single player is left standing
+        let alive = self
+            .players
+            .iter()
+            .filter(|x| x.effects.death_time.is_none())
+            .count();
+        let waited = |x: &Option<std::time::Instant>, ms| {
+            x.is_none_or(|x| {
+                std::time::Instant::now().duration_since(x) > std::time::Duration::from_millis(ms)
+            })
+        };
+        let over = self.exiting
+            || self.restarting
+            || (self.pause.is_none() && self.finished.is_some() && waited(&self.finished, 1500))
+            || (self.pause.is_none()
+                && alive < self.players.len().min(2)
+                && self
+                    .players
+                    .iter()
+                    .all(|i| waited(&i.effects.death_time, 1000)));
+        if !over {
+            return None;
+        }
+
+        if self.playback.is_some() {
+            return Some(crate::Screen::Replays);
+        }
+        if self.online.is_some() {
+            // the garbage comes from the network, so the game can't be replayed
             return Some(crate::Screen::Menu);
         }
-        for i in &mut self.boards {
-            if let Some(x) = i.borrow().death_time {
-                if std::time::Instant::now().duration_since(x)
-                    > std::time::Duration::from_millis(1000)
-                {
-                    return Some(crate::Screen::Menu);
-                }
-            }
+
+        // games which have been restarted or left aren't worth watching again
+        if self.restarting {
+            return Some(self.again());
+        }
+        // a split screen game which has been left doesn't count for the series or the records
+        if self.exiting && self.goal().is_none() {
+            return Some(crate::Screen::Menu);
+        }
+        self.replay.ticks = self.tick;
+        if let Err(e) = self.replay.save() {
+            eprintln!("Unable to save replay: {}", e);
+        }
+        let (records, best) = self.record();
+        if let Some(goal) = self.goal() {
+            return Some(crate::Screen::Results(self.results(goal, &records, best)));
+        }
+        if let (Some(series), Some(winner)) = (&mut self.series, self.winner) {
+            series.wins[winner] += 1;
         }
-        None
+        Some(crate::Screen::Results(self.split_results()))
     }
 }
@@ -0,0 +1,122 @@
+use super::*;
+use crate::net::{Client, Message};
+
+/// How often the state of the local board is sent to the opponent, in ticks
+const SNAPSHOT_INTERVAL: u64 = 3;
+
+/// The connection to the opponent in an online match.
+///
+/// The first player of the game is the local board, the second one mirrors the opponent's board.
+pub struct Online {
+    client: Client,
+    room: String,
+    /// The name of the opponent, known once the match has started
+    pub opponent: Option<String>,
+    /// Whether the opponent knows that we have topped out
+    topped_out: bool,
+    /// Set when the match can't go on
+    pub error: Option<String>,
+}
+
+impl Online {
+    pub fn connect() -> std::io::Result<Self> {
+        let room = crate::net::room();
+        Ok(Self {
+            client: Client::connect(
+                &crate::net::relay_address(),
+                &room,
+                &crate::net::player_name(),
+            )?,
+            room,
+            opponent: None,
+            topped_out: false,
+            error: None,
+        })
+    }
+
+    /// The text shown over the game
+    pub fn status(&self) -> String {
+        if let Some(x) = &self.error {
+            format!("{}, press ESCAPE to leave", x)
+        } else if let Some(x) = &self.opponent {
+            format!("VS {}", x)
+        } else {
+            format!("waiting for an opponent in room {}", self.room)
+        }
+    }
+}
+
+impl Game {
+    /// Exchange messages with the opponent
+    ///
+    /// # Return value
+    ///
+    /// Returns whether the local board may run
+    pub(super) fn update_online(&mut self) -> bool {
+        let online = match &mut self.online {
+            Some(x) => x,
+            None => return true,
+        };
+        if online.error.is_some() {
+            return false;
+        }
+
+        let messages = match online.client.poll() {
+            Ok(x) => x,
+            Err(e) => {
+                online.error = Some(format!("connection lost: {}", e));
+                return false;
+            }
+        };
+        for message in messages {
+            match message {
+                Message::Start { seed, opponent } => {
+                    online.opponent = Some(opponent);
+                    self.seed = seed;
+                    for (player, handling) in self.players.iter_mut().zip(&self.replay.handling) {
+                        player.reset(Board::new(
+                            seed,
+                            *handling,
+                            self.rotation.clone(),
+                            self.rules.clone(),
+                        ));
+                    }
+                }
+                Message::Garbage(n) => self.players[0].board.receive_garbage(n),
+                Message::Snapshot(x) => {
+                    if self.players[1].board.restore(&x).is_none() {
+                        eprintln!("Received an invalid snapshot");
+                    }
+                }
+                Message::ToppedOut | Message::Left => {
+                    self.players[1].effects.handle(&board::Event::ToppedOut);
+                }
+                Message::Error(e) => online.error = Some(e),
+                Message::Join { .. } => (),
+            }
+        }
+        online.opponent.is_some() && online.error.is_none()
+    }
+
+    /// Tell the opponent what happened during the last tick
+    ///
+    /// * `sent` - the garbage lines sent by the local board
+    pub(super) fn send_online(&mut self, sent: u32) {
+        let online = match &mut self.online {
+            Some(x) => x,
+            None => return,
+        };
+        let board = &self.players[0].board;
+        if sent > 0 {
+            online.client.send(&Message::Garbage(sent));
+        }
+        let dead = board.death_tick.is_some();
+        if self.tick.is_multiple_of(SNAPSHOT_INTERVAL) || (dead && !online.topped_out) {
+            online.client.send(&Message::Snapshot(board.snapshot()));
+        }
+        if dead && !online.topped_out {
+            online.client.send(&Message::ToppedOut);
+            online.topped_out = true;
+        }
+    }
+}
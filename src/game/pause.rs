@@ -0,0 +1,50 @@
+use glutin::event::VirtualKeyCode;
+
+/// What can be chosen in the pause menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Choice {
+    Resume,
+    Restart,
+    Quit,
+}
+
+impl Choice {
+    pub const ALL: [Choice; 3] = [Choice::Resume, Choice::Restart, Choice::Quit];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Choice::Resume => "resume",
+            Choice::Restart => "restart",
+            Choice::Quit => "quit",
+        }
+    }
+}
+
+/// The menu shown over a paused game
+pub struct Pause {
+    /// When the game has been paused, the timers of the effects are moved by the time spent
+    /// paused once it resumes
+    pub since: std::time::Instant,
+    pub selected: usize,
+}
+
+impl Pause {
+    pub fn new() -> Self {
+        Self {
+            since: std::time::Instant::now(),
+            selected: 0,
+        }
+    }
+
+    /// Handle a key press, from the keyboard or from a gamepad
+    pub fn press(&mut self, x: VirtualKeyCode) -> Option<Choice> {
+        match x {
+            VirtualKeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            VirtualKeyCode::Down => self.selected = (self.selected + 1).min(Choice::ALL.len() - 1),
+            VirtualKeyCode::Return => return Some(Choice::ALL[self.selected]),
+            VirtualKeyCode::Escape => return Some(Choice::Resume),
+            _ => (),
+        }
+        None
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+/// What holds down a key of the board. The board lets go of a key once nothing holds it anymore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Keyboard(glutin::event::VirtualKeyCode),
+    Gamepad,
+}
+
+/// The keys of a board being held, and what holds them
+#[derive(Default)]
+struct HeldKeys(Vec<(keys::Key, Source)>);
+
+impl HeldKeys {
+    /// Press or release a key from a source, and return whether the board has to change: only
+    /// the first source pressing a key and the last one releasing it count, repeats don't
+    fn change(&mut self, key: keys::Key, pressed: bool, source: Source) -> bool {
+        if self.0.contains(&(key, source)) == pressed {
+            return false;
+        }
+        if pressed {
+            self.0.push((key, source));
+        } else {
+            self.0.retain(|x| *x != (key, source));
+        }
+        self.0.iter().filter(|(x, _)| *x == key).count() == pressed as usize
+    }
+}
+
+/// A board together with everything needed to present it to a person sitting at the keyboard
+pub struct Player {
+    pub board: Board,
+    pub effects: board::BoardEffects,
+    pub stats: board::Stats,
+    audio: board::BoardAudio,
+    keybinds: keys::KeyBinds,
+    /// The gamepad assigned to this player, if any
+    pub gamepad: Option<crate::gamepad::Controller>,
+    /// How the player picks who receives their garbage
+    pub targeting: targeting::Targeting,
+    /// The index of the player who has received our garbage last
+    pub target: Option<usize>,
+    /// The board is played on another computer, and only mirrors snapshots sent over the network
+    pub remote: bool,
+    /// Set while the game is paused, held keys can be released but no key can be pressed
+    pub frozen: bool,
+    held: HeldKeys,
+}
+
+impl Player {
+    pub fn new(
+        board: Board,
+        keybinds: keys::KeyBinds,
+        audio: Rc<RefCell<kira::manager::AudioManager>>,
+        roman: &crate::resource::ResourceManager,
+    ) -> Self {
+        Self {
+            board,
+            effects: board::BoardEffects::new(0.1, 0.5, 0.1, 0.5),
+            stats: board::Stats::new(),
+            audio: board::BoardAudio::new(audio, roman),
+            keybinds,
+            gamepad: None,
+            targeting: targeting::Targeting::Even,
+            target: None,
+            remote: false,
+            frozen: false,
+            held: HeldKeys::default(),
+        }
+    }
+
+    /// Start over with a new board
+    pub fn reset(&mut self, board: Board) {
+        self.board = board;
+        self.effects.reset();
+        self.stats = board::Stats::new();
+        self.targeting = targeting::Targeting::Even;
+        self.target = None;
+        self.held = HeldKeys::default();
+    }
+
+    /// Pass a keyboard key to the board if it belongs to this player
+    ///
+    /// # Return value
+    ///
+    /// See [`Player::key`]
+    pub fn input(
+        &mut self,
+        key: glutin::event::VirtualKeyCode,
+        state: glutin::event::ElementState,
+    ) -> Option<keys::Key> {
+        let pressed = matches!(state, glutin::event::ElementState::Pressed);
+        self.key(self.keybinds.decode(key)?, pressed, Source::Keyboard(key))
+    }
+
+    /// Pass the event of the player's gamepad to the board
+    ///
+    /// # Return value
+    ///
+    /// Returns the keys returned by [`Player::key`], with whether they have been pressed
+    pub fn gamepad_input(&mut self, event: &crate::gamepad::Event) -> Vec<(keys::Key, bool)> {
+        let changes = match &mut self.gamepad {
+            Some(x) if x.device == event.device() => x.translate(event),
+            _ => return Vec::new(),
+        };
+        changes
+            .into_iter()
+            .filter_map(|(key, pressed)| Some((self.key(key, pressed, Source::Gamepad)?, pressed)))
+            .collect()
+    }
+
+    /// Press or release a key of the board
+    ///
+    /// # Return value
+    ///
+    /// Returns the key when the board's input has changed, key repeats and keys held by another
+    /// source too are ignored. Keys which control the whole game, like pausing, are returned when
+    /// pressed and never reach the board.
+    pub fn key(&mut self, key: keys::Key, pressed: bool, source: Source) -> Option<keys::Key> {
+        if self.remote {
+            return None;
+        }
+        if matches!(key, keys::Key::Restart | keys::Key::Pause) {
+            return pressed.then_some(key);
+        }
+        if (pressed && self.frozen) || !self.held.change(key, pressed, source) {
+            return None;
+        }
+        if pressed {
+            self.board.press(key)
+        } else {
+            self.board.release(key)
+        }
+        Some(key)
+    }
+
+    /// Advance the board by one tick and let the effects, audio and statistics react to what
+    /// happened.
+    ///
+    /// * `silent` - don't play any sounds
+    ///
+    /// # Return value
+    ///
+    /// Returns the amount of garbage lines the board has sent
+    pub fn update(&mut self, silent: bool) -> u32 {
+        if !self.remote {
+            self.board.tick();
+        }
+        self.effects.update();
+        if self.board.death_tick.is_none() {
+            self.stats.tick();
+        }
+
+        let mut sent = 0;
+        for event in self.board.drain_events() {
+            self.effects.handle(&event);
+            self.stats.handle(&event);
+            if !silent {
+                self.audio.handle(&event);
+            }
+            if let board::Event::GarbageSent(n) = event {
+                sent += n;
+            }
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glutin::event::VirtualKeyCode;
+
+    #[test]
+    fn last_source_releases_the_key() {
+        let mut held = HeldKeys::default();
+        let a = Source::Keyboard(VirtualKeyCode::Left);
+        let b = Source::Keyboard(VirtualKeyCode::J);
+
+        assert!(held.change(keys::Key::Left, true, a));
+        // a repeat of the keyboard
+        assert!(!held.change(keys::Key::Left, true, a));
+        assert!(!held.change(keys::Key::Left, true, b));
+        assert!(!held.change(keys::Key::Left, true, Source::Gamepad));
+        assert!(!held.change(keys::Key::Left, false, a));
+        assert!(!held.change(keys::Key::Left, false, Source::Gamepad));
+        assert!(held.change(keys::Key::Left, false, b));
+        // a key which isn't held can't be released
+        assert!(!held.change(keys::Key::Left, false, b));
+    }
+}
@@ -0,0 +1,270 @@
+use super::board::{Handling, Rules};
+use super::keys::Key;
+use super::GameMode;
+use std::path::{Path, PathBuf};
+
+/// The version of the replay files written by this build.
+///
+/// Bump this whenever a change to the game would make older replays play out differently.
+pub const REPLAY_VERSION: u32 = 10;
+
+/// A key being pressed or released on a given tick
+#[derive(Clone, Copy, Debug)]
+pub struct KeyEvent {
+    /// The number of ticks which have passed before the key has been pressed
+    pub tick: u64,
+    pub key: Key,
+    pub pressed: bool,
+}
+
+/// Everything needed to play a game again: the seed, the mode, the rotation system, the rules,
+/// and the handling and the inputs of every board
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GameMode,
+    /// The name of the rotation system
+    pub rotation: String,
+    pub rules: Rules,
+    pub handling: Vec<Handling>,
+    /// The length of the game in ticks
+    pub ticks: u64,
+    /// The inputs of every board, sorted by tick
+    pub inputs: Vec<Vec<KeyEvent>>,
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        mode: GameMode,
+        rotation: String,
+        rules: Rules,
+        handling: Vec<Handling>,
+    ) -> Self {
+        Self {
+            seed,
+            mode,
+            rotation,
+            rules,
+            ticks: 0,
+            inputs: vec![Vec::new(); handling.len()],
+            handling,
+        }
+    }
+
+    /// The directory where the replays are stored
+    pub fn directory() -> PathBuf {
+        crate::data_dir().join("replays")
+    }
+
+    /// List all the saved replays, newest first
+    pub fn list() -> Vec<PathBuf> {
+        let mut files = match std::fs::read_dir(Self::directory()) {
+            Ok(dir) => dir
+                .filter_map(|x| x.ok())
+                .map(|x| x.path())
+                .filter(|x| x.extension().is_some_and(|x| x == "replay"))
+                .collect::<Vec<PathBuf>>(),
+            Err(_) => Vec::new(),
+        };
+        files.sort();
+        files.reverse();
+        files
+    }
+
+    /// Save the replay into the replay directory, named after the current time. Replays saved
+    /// within the same second get a number after the time instead of replacing each other.
+    pub fn save(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        use std::io::Write;
+
+        let directory = Self::directory();
+        std::fs::create_dir_all(&directory)?;
+        let timestamp = crate::timestamp();
+        let mut i = 1;
+        loop {
+            let file = directory.join(if i == 1 {
+                format!("{}.replay", timestamp)
+            } else {
+                format!("{}_{}.replay", timestamp, i)
+            });
+            let open = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&file);
+            match open {
+                Ok(mut handle) => {
+                    handle.write_all(self.to_json().dump().as_bytes())?;
+                    return Ok(file);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => i += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Read what the list of replays shows, without loading the inputs. The fields written
+    /// before the handling and the inputs are enough, so only the start of the file is read.
+    pub fn info(file: &Path) -> Result<ReplayInfo, Box<dyn std::error::Error>> {
+        use std::io::Read;
+
+        const HEADER_BYTES: u64 = 16 * 1024;
+        let mut start = Vec::new();
+        std::fs::File::open(file)?
+            .take(HEADER_BYTES)
+            .read_to_end(&mut start)?;
+        let start = String::from_utf8_lossy(&start);
+        let end = ["\"handling\":", "\"boards\":"]
+            .iter()
+            .filter_map(|key| start.find(key))
+            .min()
+            .ok_or("not a replay file")?;
+        let data = json::parse(&format!("{}}}", start[..end].trim_end_matches(',')))?;
+        Ok(ReplayInfo {
+            version: data["version"].as_u32().ok_or("not a replay file")?,
+            mode: data["mode"].as_str().and_then(GameMode::from_name),
+            ticks: data["ticks"].as_u64().unwrap_or(0),
+        })
+    }
+
+    pub fn load(file: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_json(&json::parse(&std::fs::read_to_string(file)?)?)
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            version: REPLAY_VERSION,
+            seed: self.seed,
+            mode: self.mode.name(),
+            rotation: self.rotation.as_str(),
+            rules: self.rules.to_json(),
+            ticks: self.ticks,
+            handling: self.handling.iter().map(|x| x.to_json()).collect::<Vec<json::JsonValue>>(),
+            boards: self.inputs.iter().map(|board| {
+                board
+                    .iter()
+                    .map(|x| json::array![x.tick, x.key.name(), x.pressed])
+                    .collect::<Vec<json::JsonValue>>()
+            }).collect::<Vec<Vec<json::JsonValue>>>(),
+        }
+    }
+
+    fn from_json(data: &json::JsonValue) -> Result<Self, Box<dyn std::error::Error>> {
+        match data["version"].as_u32() {
+            Some(REPLAY_VERSION) => (),
+            Some(x) => return Err(format!("unsupported replay version {}", x).into()),
+            None => return Err("not a replay file".into()),
+        }
+        let inputs = data["boards"]
+            .members()
+            .map(|board| {
+                board
+                    .members()
+                    .map(|x| {
+                        Some(KeyEvent {
+                            tick: x[0].as_u64()?,
+                            key: Key::from_name(x[1].as_str()?)?,
+                            pressed: x[2].as_bool()?,
+                        })
+                    })
+                    .collect::<Option<Vec<KeyEvent>>>()
+            })
+            .collect::<Option<Vec<Vec<KeyEvent>>>>()
+            .ok_or("invalid input in replay")?;
+        let handling = (0..inputs.len())
+            .map(|i| Handling::from_json(&data["handling"][i]))
+            .collect();
+        Ok(Self {
+            seed: data["seed"].as_u64().ok_or("invalid seed in replay")?,
+            mode: GameMode::from_name(data["mode"].as_str().unwrap_or(""))
+                .ok_or("invalid game mode in replay")?,
+            rotation: String::from(
+                data["rotation"]
+                    .as_str()
+                    .ok_or("invalid rotation system in replay")?,
+            ),
+            rules: Rules::from_json(
+                data["rules"]["name"].as_str().unwrap_or("tetr.io"),
+                &data["rules"],
+            )?,
+            ticks: data["ticks"].as_u64().ok_or("invalid length of replay")?,
+            handling,
+            inputs,
+        })
+    }
+}
+
+/// What the list of replays shows about a replay file
+pub struct ReplayInfo {
+    pub version: u32,
+    /// `None` for modes this build doesn't know
+    pub mode: Option<GameMode>,
+    /// The length of the game in ticks
+    pub ticks: u64,
+}
+
+impl ReplayInfo {
+    /// Whether this build can play the replay
+    pub fn playable(&self) -> bool {
+        self.version == REPLAY_VERSION
+    }
+}
+
+/// The state of a replay being watched
+pub struct Playback {
+    /// The index of the next input to be applied, for every board
+    pub next_input: Vec<usize>,
+    /// The playback speed, in half ticks per tick
+    pub speed: u32,
+    /// Half ticks which haven't been played yet
+    pub progress: u32,
+    pub paused: bool,
+}
+
+impl Playback {
+    pub fn new(boards: usize) -> Self {
+        Self {
+            next_input: vec![0; boards],
+            speed: 2,
+            progress: 0,
+            paused: false,
+        }
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2).min(4);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2).max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_reads_the_header() {
+        let mut replay = Replay::new(
+            7,
+            GameMode::Split(3),
+            String::from("srs+"),
+            Rules::default(),
+            vec![Handling::default(); 3],
+        );
+        replay.ticks = 1234;
+        replay.inputs[0].push(KeyEvent {
+            tick: 5,
+            key: Key::Left,
+            pressed: true,
+        });
+        let file = std::env::temp_dir().join(format!("tetr-test-{}.replay", std::process::id()));
+        std::fs::write(&file, replay.to_json().dump()).unwrap();
+        let info = Replay::info(&file);
+        std::fs::remove_file(&file).unwrap();
+
+        let info = info.unwrap();
+        assert!(info.playable());
+        assert_eq!(info.mode, Some(GameMode::Split(3)));
+        assert_eq!(info.ticks, 1234);
+    }
+}
@@ -0,0 +1,530 @@
+use super::tetromino::Shape;
+use super::util::BlockPos;
+
+/// The names of the rotation systems built into the game
+pub const BUILT_IN: [&str; 4] = ["srs", "srs+", "ars", "nrs"];
+
+/// The directory where custom rotation systems are looked for, one JSON file per system
+pub fn custom_dir() -> std::path::PathBuf {
+    crate::config_dir().join("rotation")
+}
+
+/// A way of rotating a piece: clockwise, counter-clockwise or by 180 degrees
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Clockwise,
+    CounterClockwise,
+    Half,
+}
+
+impl Rotation {
+    const ALL: [Rotation; 3] = [
+        Rotation::Clockwise,
+        Rotation::CounterClockwise,
+        Rotation::Half,
+    ];
+
+    /// The number of clockwise quarter turns
+    pub fn turns(self) -> u8 {
+        match self {
+            Rotation::Clockwise => 1,
+            Rotation::CounterClockwise => 3,
+            Rotation::Half => 2,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Rotation::Clockwise => "cw",
+            Rotation::CounterClockwise => "ccw",
+            Rotation::Half => "180",
+        }
+    }
+}
+
+/// How a single piece looks and rotates
+#[derive(Clone, Debug)]
+struct Piece {
+    /// The blocks of every rotation state inside a 4×4 box, with `y` going up
+    states: [[BlockPos; 4]; 4],
+    /// The bottom left corner of the box when the piece appears
+    spawn: BlockPos,
+    /// The offsets tried one by one when rotating out of a state, for every [`Rotation`]
+    kicks: [[Vec<BlockPos>; 3]; 4],
+}
+
+/// The shapes, spawn positions and kick tables of all the pieces
+#[derive(Clone, Debug)]
+pub struct RotationSystem {
+    pub name: String,
+    pieces: [Piece; 7],
+    /// Arika's rule: L, J and T don't kick when the first blocked cell is in the middle column
+    center_column_rule: bool,
+}
+
+/// Turn a picture of a piece, from the top row down, into the positions of its blocks
+fn parse_state(rows: &[&str]) -> Option<[BlockPos; 4]> {
+    let mut blocks = Vec::new();
+    for (y, row) in rows.iter().enumerate().take(4) {
+        for (x, c) in row.chars().enumerate().take(4) {
+            if c == '#' {
+                blocks.push(BlockPos::new(x as i32, 3 - y as i32));
+            }
+        }
+    }
+    blocks.try_into().ok()
+}
+
+fn states(pictures: [[&str; 4]; 4]) -> [[BlockPos; 4]; 4] {
+    pictures.map(|x| parse_state(&x).expect("built in pieces have 4 blocks"))
+}
+
+fn offsets(kicks: &[(i32, i32)]) -> Vec<BlockPos> {
+    kicks.iter().map(|(x, y)| BlockPos::new(*x, *y)).collect()
+}
+
+/// The same kicks for every rotation out of every state
+fn same_kicks(kicks: &[(i32, i32)]) -> [[Vec<BlockPos>; 3]; 4] {
+    std::array::from_fn(|_| std::array::from_fn(|_| offsets(kicks)))
+}
+
+/// Kicks which depend on the state being left, `cw[x]` and `ccw[x]` are used when rotating out
+/// of state `x`
+fn srs_kicks(
+    cw: [&[(i32, i32)]; 4],
+    ccw: [&[(i32, i32)]; 4],
+    half: [&[(i32, i32)]; 4],
+) -> [[Vec<BlockPos>; 3]; 4] {
+    std::array::from_fn(|x| [offsets(cw[x]), offsets(ccw[x]), offsets(half[x])])
+}
+
+/// The kicks of the J, L, S, T and Z pieces in SRS
+const SRS_CW: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const SRS_CCW: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const SRS_I_CW: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    &[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    &[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+const SRS_I_CCW: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    &[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    &[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    &[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+];
+/// The I kicks of tetr.io, which mirror each other when rotating the other way from the mirrored
+/// state
+const SRS_PLUS_I_CW: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)],
+    &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    &[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    &[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+const SRS_PLUS_I_CCW: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)],
+    &[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    &[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    &[(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+/// SRS has no 180 rotation, only the unkicked one is allowed
+const NO_KICKS: [&[(i32, i32)]; 4] = [&[(0, 0)], &[(0, 0)], &[(0, 0)], &[(0, 0)]];
+/// The 180 kicks of tetr.io
+const SRS_PLUS_HALF: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    &[(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    &[(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    &[(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+impl RotationSystem {
+    /// The Super Rotation System of the Tetris guideline
+    pub fn srs() -> Self {
+        let piece = |pictures, cw, ccw| Piece {
+            states: states(pictures),
+            spawn: BlockPos::new(3, 20),
+            kicks: srs_kicks(cw, ccw, NO_KICKS),
+        };
+        Self {
+            name: String::from("srs"),
+            pieces: [
+                piece(
+                    [
+                        ["    ", "####", "    ", "    "],
+                        ["  # ", "  # ", "  # ", "  # "],
+                        ["    ", "    ", "####", "    "],
+                        [" #  ", " #  ", " #  ", " #  "],
+                    ],
+                    SRS_I_CW,
+                    SRS_I_CCW,
+                ),
+                piece(
+                    [
+                        ["#   ", "### ", "    ", "    "],
+                        [" ## ", " #  ", " #  ", "    "],
+                        ["    ", "### ", "  # ", "    "],
+                        [" #  ", " #  ", "##  ", "    "],
+                    ],
+                    SRS_CW,
+                    SRS_CCW,
+                ),
+                piece(
+                    [
+                        ["  # ", "### ", "    ", "    "],
+                        [" #  ", " #  ", " ## ", "    "],
+                        ["    ", "### ", "#   ", "    "],
+                        ["##  ", " #  ", " #  ", "    "],
+                    ],
+                    SRS_CW,
+                    SRS_CCW,
+                ),
+                piece([[" ## ", " ## ", "    ", "    "]; 4], NO_KICKS, NO_KICKS),
+                piece(
+                    [
+                        [" ## ", "##  ", "    ", "    "],
+                        [" #  ", " ## ", "  # ", "    "],
+                        ["    ", " ## ", "##  ", "    "],
+                        ["#   ", "##  ", " #  ", "    "],
+                    ],
+                    SRS_CW,
+                    SRS_CCW,
+                ),
+                piece(
+                    [
+                        [" #  ", "### ", "    ", "    "],
+                        [" #  ", " ## ", " #  ", "    "],
+                        ["    ", "### ", " #  ", "    "],
+                        [" #  ", "##  ", " #  ", "    "],
+                    ],
+                    SRS_CW,
+                    SRS_CCW,
+                ),
+                piece(
+                    [
+                        ["##  ", " ## ", "    ", "    "],
+                        ["  # ", " ## ", " #  ", "    "],
+                        ["    ", "##  ", " ## ", "    "],
+                        [" #  ", "##  ", "#   ", "    "],
+                    ],
+                    SRS_CW,
+                    SRS_CCW,
+                ),
+            ],
+            center_column_rule: false,
+        }
+    }
+
+    /// SRS with the I and 180 kicks of tetr.io
+    pub fn srs_plus() -> Self {
+        let mut system = Self::srs();
+        system.name = String::from("srs+");
+        system.pieces[Shape::I as usize].kicks = srs_kicks(SRS_PLUS_I_CW, SRS_PLUS_I_CCW, NO_KICKS);
+        for shape in [Shape::I, Shape::J, Shape::L, Shape::S, Shape::T, Shape::Z] {
+            let piece = &mut system.pieces[shape as usize];
+            for (state, kicks) in piece.kicks.iter_mut().enumerate() {
+                kicks[2] = offsets(SRS_PLUS_HALF[state]);
+            }
+        }
+        system
+    }
+
+    /// The Arika Rotation System of the TGM games. The pieces rest on the bottom of their box
+    /// and kick by one block to the right or to the left, except the I which doesn't kick at all.
+    pub fn ars() -> Self {
+        let kicks = same_kicks(&[(0, 0), (1, 0), (-1, 0)]);
+        let piece = |pictures, kicks: &[[Vec<BlockPos>; 3]; 4]| Piece {
+            states: states(pictures),
+            spawn: BlockPos::new(3, 21),
+            kicks: kicks.clone(),
+        };
+        Self {
+            name: String::from("ars"),
+            pieces: [
+                piece(
+                    [
+                        ["    ", "####", "    ", "    "],
+                        ["  # ", "  # ", "  # ", "  # "],
+                        ["    ", "####", "    ", "    "],
+                        ["  # ", "  # ", "  # ", "  # "],
+                    ],
+                    &same_kicks(&[(0, 0)]),
+                ),
+                piece(
+                    [
+                        ["    ", "### ", "  # ", "    "],
+                        [" #  ", " #  ", "##  ", "    "],
+                        ["    ", "#   ", "### ", "    "],
+                        [" ## ", " #  ", " #  ", "    "],
+                    ],
+                    &kicks,
+                ),
+                piece(
+                    [
+                        ["    ", "### ", "#   ", "    "],
+                        ["##  ", " #  ", " #  ", "    "],
+                        ["    ", "  # ", "### ", "    "],
+                        [" #  ", " #  ", " ## ", "    "],
+                    ],
+                    &kicks,
+                ),
+                piece(
+                    [["    ", " ## ", " ## ", "    "]; 4],
+                    &same_kicks(&[(0, 0)]),
+                ),
+                piece(
+                    [
+                        ["    ", " ## ", "##  ", "    "],
+                        ["#   ", "##  ", " #  ", "    "],
+                        ["    ", " ## ", "##  ", "    "],
+                        ["#   ", "##  ", " #  ", "    "],
+                    ],
+                    &kicks,
+                ),
+                piece(
+                    [
+                        ["    ", "### ", " #  ", "    "],
+                        [" #  ", "##  ", " #  ", "    "],
+                        ["    ", " #  ", "### ", "    "],
+                        [" #  ", " ## ", " #  ", "    "],
+                    ],
+                    &kicks,
+                ),
+                piece(
+                    [
+                        ["    ", "##  ", " ## ", "    "],
+                        ["  # ", " ## ", " #  ", "    "],
+                        ["    ", "##  ", " ## ", "    "],
+                        ["  # ", " ## ", " #  ", "    "],
+                    ],
+                    &kicks,
+                ),
+            ],
+            center_column_rule: true,
+        }
+    }
+
+    /// The Nintendo Rotation System of the NES game, the pieces turn around a fixed block and
+    /// never kick
+    pub fn nrs() -> Self {
+        let piece = |pictures| Piece {
+            states: states(pictures),
+            spawn: BlockPos::new(3, 21),
+            kicks: same_kicks(&[(0, 0)]),
+        };
+        Self {
+            name: String::from("nrs"),
+            pieces: [
+                piece([
+                    ["    ", "    ", "####", "    "],
+                    ["  # ", "  # ", "  # ", "  # "],
+                    ["    ", "    ", "####", "    "],
+                    ["  # ", "  # ", "  # ", "  # "],
+                ]),
+                piece([
+                    ["    ", "### ", "  # ", "    "],
+                    [" #  ", " #  ", "##  ", "    "],
+                    ["#   ", "### ", "    ", "    "],
+                    [" ## ", " #  ", " #  ", "    "],
+                ]),
+                piece([
+                    ["    ", "### ", "#   ", "    "],
+                    ["##  ", " #  ", " #  ", "    "],
+                    ["  # ", "### ", "    ", "    "],
+                    [" #  ", " #  ", " ## ", "    "],
+                ]),
+                piece([["    ", " ## ", " ## ", "    "]; 4]),
+                piece([
+                    ["    ", " ## ", "##  ", "    "],
+                    [" #  ", " ## ", "  # ", "    "],
+                    ["    ", " ## ", "##  ", "    "],
+                    [" #  ", " ## ", "  # ", "    "],
+                ]),
+                piece([
+                    ["    ", "### ", " #  ", "    "],
+                    [" #  ", "##  ", " #  ", "    "],
+                    [" #  ", "### ", "    ", "    "],
+                    [" #  ", " ## ", " #  ", "    "],
+                ]),
+                piece([
+                    ["    ", "##  ", " ## ", "    "],
+                    ["  # ", " ## ", " #  ", "    "],
+                    ["    ", "##  ", " ## ", "    "],
+                    ["  # ", " ## ", " #  ", "    "],
+                ]),
+            ],
+            center_column_rule: false,
+        }
+    }
+
+    /// Find a rotation system by its name, either built in or in [`custom_dir`]
+    pub fn by_name(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match name {
+            "srs" => Ok(Self::srs()),
+            "srs+" => Ok(Self::srs_plus()),
+            "ars" => Ok(Self::ars()),
+            "nrs" => Ok(Self::nrs()),
+            name => {
+                let data = std::fs::read_to_string(custom_dir().join(format!("{}.json", name)))?;
+                Ok(Self::from_json(name, &json::parse(&data)?)?)
+            }
+        }
+    }
+
+    /// The names of all the rotation systems which can be chosen, the custom ones last
+    pub fn available() -> Vec<String> {
+        let mut names = BUILT_IN.map(String::from).to_vec();
+        let mut custom = std::fs::read_dir(custom_dir())
+            .map(|x| {
+                x.flatten()
+                    .filter_map(|x| {
+                        let path = x.path();
+                        if path.extension()? != "json" {
+                            return None;
+                        }
+                        Some(path.file_stem()?.to_str()?.to_string())
+                    })
+                    .filter(|x| !BUILT_IN.contains(&x.as_str()))
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        custom.sort();
+        names.extend(custom);
+        names
+    }
+
+    /// Read a custom rotation system. Everything it doesn't mention is taken from the built in
+    /// system named in `base`, SRS by default.
+    ///
+    /// The pieces are given by their letters, a key like `"JLSTZ"` applies to all of them.
+    pub fn from_json(name: &str, data: &json::JsonValue) -> Result<Self, String> {
+        let base = data["base"].as_str().unwrap_or("srs");
+        if !BUILT_IN.contains(&base) {
+            return Err(format!("Unknown base rotation system {}", base));
+        }
+        let mut system = Self::by_name(base).map_err(|e| e.to_string())?;
+        system.name = String::from(name);
+        if let Some(x) = data["center_column_rule"].as_bool() {
+            system.center_column_rule = x;
+        }
+
+        // the pieces named by a key
+        let shapes = |key: &str| -> Result<Vec<Shape>, String> {
+            key.chars()
+                .map(|x| Shape::from_letter(x).ok_or(format!("Unknown piece {}", x)))
+                .collect()
+        };
+        let position = |data: &json::JsonValue| -> Option<BlockPos> {
+            Some(BlockPos::new(data[0].as_i32()?, data[1].as_i32()?))
+        };
+
+        for (key, states) in data["shapes"].entries() {
+            if states.len() != 4 {
+                return Err(format!("The shape of {} needs 4 rotation states", key));
+            }
+            let mut parsed = [[BlockPos::new(0, 0); 4]; 4];
+            for (i, rows) in states.members().enumerate() {
+                let rows = rows
+                    .members()
+                    .filter_map(|x| x.as_str())
+                    .collect::<Vec<_>>();
+                parsed[i] = parse_state(&rows)
+                    .ok_or(format!("State {} of {} doesn't have 4 blocks", i, key))?;
+            }
+            for shape in shapes(key)? {
+                system.pieces[shape as usize].states = parsed;
+            }
+        }
+
+        for (key, spawn) in data["spawn"].entries() {
+            let spawn = position(spawn).ok_or(format!("Invalid spawn position of {}", key))?;
+            for shape in shapes(key)? {
+                system.pieces[shape as usize].spawn = spawn;
+            }
+        }
+
+        for (key, kicks) in data["kicks"].entries() {
+            for rotation in Rotation::ALL {
+                let tables = &kicks[rotation.name()];
+                if tables.is_null() {
+                    continue;
+                }
+                if tables.len() != 4 {
+                    return Err(format!(
+                        "The {} kicks of {} need a list for every state",
+                        rotation.name(),
+                        key
+                    ));
+                }
+                for (state, table) in tables.members().enumerate() {
+                    let offsets = table
+                        .members()
+                        .map(position)
+                        .collect::<Option<Vec<BlockPos>>>()
+                        .ok_or(format!("Invalid {} kick of {}", rotation.name(), key))?;
+                    for shape in shapes(key)? {
+                        system.pieces[shape as usize].kicks[state][rotation as usize] =
+                            offsets.clone();
+                    }
+                }
+            }
+        }
+        Ok(system)
+    }
+
+    /// The blocks of a piece in a rotation state, relative to the bottom left corner of its box
+    pub fn blocks(&self, shape: Shape, state: u8) -> &[BlockPos; 4] {
+        &self.pieces[shape as usize].states[state as usize % 4]
+    }
+
+    /// Where the bottom left corner of the piece's box is when it appears
+    pub fn spawn(&self, shape: Shape) -> BlockPos {
+        self.pieces[shape as usize].spawn
+    }
+
+    /// The offsets to try one by one when rotating a piece out of a state
+    pub fn kicks(&self, shape: Shape, state: u8, rotation: Rotation) -> &[BlockPos] {
+        &self.pieces[shape as usize].kicks[state as usize % 4][rotation as usize]
+    }
+
+    /// Whether the rotation may kick at all, given the blocks of the rotated piece which are
+    /// obstructed
+    pub fn may_kick(&self, shape: Shape, obstructed: &[BlockPos]) -> bool {
+        if !self.center_column_rule || !matches!(shape, Shape::J | Shape::L | Shape::T) {
+            return true;
+        }
+        // the first blocked cell, reading the box from the top left
+        obstructed
+            .iter()
+            .min_by_key(|x| (-x.y, x.x))
+            .is_none_or(|x| x.x != 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srs_plus_i_kicks_are_symmetric() {
+        let system = RotationSystem::srs_plus();
+        // flipping the board swaps the R and L states, and clockwise with counter-clockwise
+        for state in 0..4 {
+            let mirrored = (4 - state) % 4;
+            let cw = system.kicks(Shape::I, state, Rotation::Clockwise);
+            let ccw = system.kicks(Shape::I, mirrored, Rotation::CounterClockwise);
+            let flipped = cw.iter().map(|x| (-x.x, x.y)).collect::<Vec<_>>();
+            let ccw = ccw.iter().map(|x| (x.x, x.y)).collect::<Vec<_>>();
+            assert_eq!(flipped, ccw, "kicks out of state {}", state);
+        }
+    }
+}
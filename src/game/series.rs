@@ -0,0 +1,39 @@
+/// The longest series which can be chosen in the settings
+pub const MAX_BEST_OF: u32 = 9;
+
+/// Split screen games played one after another, until a player has won most of them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Series {
+    /// The most games the series can take, odd so that there is always a winner with two players
+    pub best_of: u32,
+    /// The games won by every player
+    pub wins: Vec<u32>,
+}
+
+impl Series {
+    pub fn new(players: usize, best_of: u32) -> Self {
+        Self {
+            best_of,
+            wins: vec![0; players],
+        }
+    }
+
+    /// The games a player has to win to win the series
+    pub fn needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+
+    /// The player who has won the series, if it is over
+    pub fn winner(&self) -> Option<usize> {
+        self.wins.iter().position(|x| *x >= self.needed())
+    }
+
+    /// The games won by every player, like "2 - 1"
+    pub fn score(&self) -> String {
+        self.wins
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(" - ")
+    }
+}
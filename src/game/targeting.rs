@@ -0,0 +1,102 @@
+use rand::prelude::*;
+
+/// How a player picks the opponent who receives their garbage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Targeting {
+    /// The opponent targeted by the fewest players
+    Even,
+    /// The opponent closest to topping out
+    KOs,
+    /// A random opponent for every attack
+    Random,
+    /// The opponents who are targeting us, or evenly when nobody is
+    Attackers,
+    /// A specific opponent, picked by cycling through them
+    Manual(usize),
+}
+
+/// What the targeting strategies need to know about every player
+pub struct Candidate {
+    pub alive: bool,
+    /// Who the player has attacked last
+    pub target: Option<usize>,
+    /// How close the player is to topping out
+    pub danger: u32,
+}
+
+impl Targeting {
+    /// The text shown under the board
+    pub fn label(self) -> String {
+        match self {
+            Targeting::Even => String::from("EVEN"),
+            Targeting::KOs => String::from("KOs"),
+            Targeting::Random => String::from("RANDOM"),
+            Targeting::Attackers => String::from("ATTACKERS"),
+            Targeting::Manual(x) => format!("P{}", x + 1),
+        }
+    }
+
+    /// The strategy after pressing the targeting key: first the automatic strategies and then
+    /// every living opponent one by one
+    pub fn next(self, me: usize, players: &[Candidate]) -> Self {
+        let opponent_after = |first: usize| {
+            (first..players.len())
+                .find(|x| *x != me && players[*x].alive)
+                .map_or(Targeting::Even, Targeting::Manual)
+        };
+        match self {
+            Targeting::Even => Targeting::KOs,
+            Targeting::KOs => Targeting::Random,
+            Targeting::Random => Targeting::Attackers,
+            Targeting::Attackers => opponent_after(0),
+            Targeting::Manual(x) => opponent_after(x + 1),
+        }
+    }
+
+    /// Pick the player who receives the garbage of player `me`
+    pub fn choose(self, me: usize, players: &[Candidate], rng: &mut SmallRng) -> Option<usize> {
+        let opponents = (0..players.len())
+            .filter(|x| *x != me && players[*x].alive)
+            .collect::<Vec<usize>>();
+        if opponents.is_empty() {
+            return None;
+        }
+        // how many players are targeting the given player
+        let targeted_by = |x: usize| {
+            players
+                .iter()
+                .filter(|p| p.alive && p.target == Some(x))
+                .count()
+        };
+        let even = || opponents.iter().copied().min_by_key(|x| targeted_by(*x));
+
+        match self {
+            Targeting::Even => even(),
+            Targeting::KOs => opponents
+                .iter()
+                .copied()
+                .rev()
+                .max_by_key(|x| players[*x].danger),
+            Targeting::Random => opponents.choose(rng).copied(),
+            Targeting::Attackers => {
+                let attackers = opponents
+                    .iter()
+                    .copied()
+                    .filter(|x| players[*x].target == Some(me))
+                    .collect::<Vec<usize>>();
+                if attackers.is_empty() {
+                    even()
+                } else {
+                    attackers.choose(rng).copied()
+                }
+            }
+            Targeting::Manual(x) => {
+                if opponents.contains(&x) {
+                    Some(x)
+                } else {
+                    even()
+                }
+            }
+        }
+    }
+}
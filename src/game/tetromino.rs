@@ -1,6 +1,8 @@
 use super::board::PlayingField;
+use super::rotation::{Rotation, RotationSystem};
 use super::Block;
 use crate::game::util::BlockPos;
+use std::rc::Rc;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Shape {
@@ -13,40 +15,90 @@ pub enum Shape {
     Z,
 }
 
+impl Shape {
+    pub const ALL: [Shape; 7] = [
+        Shape::I,
+        Shape::J,
+        Shape::L,
+        Shape::O,
+        Shape::S,
+        Shape::T,
+        Shape::Z,
+    ];
+
+    /// The letter of the shape, used when storing pieces as text
+    pub fn letter(self) -> char {
+        match self {
+            Shape::I => 'I',
+            Shape::J => 'J',
+            Shape::L => 'L',
+            Shape::O => 'O',
+            Shape::S => 'S',
+            Shape::T => 'T',
+            Shape::Z => 'Z',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.letter() == letter)
+    }
+
+    pub fn color(self) -> (f32, f32, f32) {
+        Tetromino::COLORS[self as usize]
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Tetromino {
-    /// Position of the top left corner
+    /// Position of the bottom left corner of the piece's box
     pub position: BlockPos,
     pub shape: Shape,
     /// Every piece has 4 rotation states.
     /// Rotations go clockwise from the start state
     rotation_state: u8,
-}
-
-/// Generates a 2D 4x4 [x][y] shape from an array of chars.
-/// Also inverts `y` so that the blocks look natually on screen.
-macro_rules! block_shape {
-    ($c:expr, [$([$($x:expr),*]),*]) => {{
-        let mut data = [[Block::Air;4];4];
-        let mut x = 0;
-        let mut y = 0;
-        $($(if $x == '#' { data[x][3-y] = Block::Block{color: $c}; }; x += 1;)* {&x}; y += 1; x = 0; )*
-        // remove annoyting "unused assignment" warnings
-        {&x};
-        {&y};
-        data
-    }}
+    /// The offset the last rotation has been kicked by, (0, 0) when it didn't kick
+    kick: BlockPos,
+    /// Decides how the piece looks and kicks
+    system: Rc<RotationSystem>,
 }
 
 impl Tetromino {
-    pub fn new(shape: Shape) -> Self {
+    /// Create a piece in its spawn position
+    pub fn new(shape: Shape, system: &Rc<RotationSystem>) -> Self {
         Self {
-            position: BlockPos::new(3, 20),
+            position: system.spawn(shape),
             shape,
             rotation_state: 0,
+            kick: BlockPos::new(0, 0),
+            system: system.clone(),
+        }
+    }
+
+    /// Create a piece in a given position and rotation state
+    pub fn placed(
+        shape: Shape,
+        position: BlockPos,
+        rotation_state: u8,
+        system: &Rc<RotationSystem>,
+    ) -> Self {
+        Self {
+            position,
+            shape,
+            rotation_state: rotation_state % 4,
+            kick: BlockPos::new(0, 0),
+            system: system.clone(),
         }
     }
 
+    pub fn rotation_state(&self) -> u8 {
+        self.rotation_state
+    }
+
+    /// The offset the last successful rotation has been kicked by
+    pub fn kick(&self) -> BlockPos {
+        self.kick
+    }
+
     /// Attempt to move the block in a given direction
     ///
     /// # Return value
@@ -64,198 +116,83 @@ impl Tetromino {
 
     /// Test whether the block intersects with something on the playing field
     pub fn obstructed(&self, game: &PlayingField) -> bool {
-        let shape = self.get_shape();
-        for x in 0..4 {
-            for y in 0..4 {
-                if let Block::Block { .. } = shape[x][y] {
-                    if x as i32 + self.position.x >= 0
-                        && x as i32 + self.position.x < 10
-                        && y as i32 + self.position.y >= 0
-                    {
-                        if let Block::Block { .. } = game[(self.position.y + y as i32) as usize]
-                            [(self.position.x + x as i32) as usize]
-                        {
-                            // We intersect with a block
-                            return true;
-                        }
-                    } else {
-                        // We are outside of the playing field
-                        return true;
-                    }
-                }
-            }
-        }
-        false
+        !self.obstructed_blocks(game).is_empty()
     }
 
-    /// Attempt to rotate the block clockwise
+    /// The blocks of the piece, relative to its box, which intersect with something on the
+    /// playing field or are outside of it
+    fn obstructed_blocks(&self, game: &PlayingField) -> Vec<BlockPos> {
+        self.system
+            .blocks(self.shape, self.rotation_state)
+            .iter()
+            .copied()
+            .filter(|block| {
+                let x = self.position.x + block.x;
+                let y = self.position.y + block.y;
+                if x < 0 || y < 0 {
+                    // We are outside of the playing field
+                    return true;
+                }
+                // We are above or right of it, or intersect with a block
+                game.get(y as usize)
+                    .and_then(|row| row.get(x as usize))
+                    .is_none_or(|block| matches!(block, Block::Block { .. }))
+            })
+            .collect()
+    }
+
+    /// Attempt to rotate the block, trying the kicks of the rotation system one by one
     ///
     /// # Return value
     ///
     /// Returns `true` when the rotation was unsuccessful
-    pub fn rotate_cw(&mut self, game: &PlayingField) -> bool {
-        self.rotation_state += 1;
-        self.rotation_state %= 4;
+    pub fn rotate(&mut self, rotation: Rotation, game: &PlayingField) -> bool {
+        let system = self.system.clone();
+        let from = self.rotation_state;
+        self.rotation_state = (from + rotation.turns()) % 4;
 
-        // test default rotation
-        if !self.obstructed(game) {
+        let obstructed = self.obstructed_blocks(game);
+        if obstructed.is_empty() {
+            self.kick = BlockPos::new(0, 0);
             return false;
         }
-
-        for i in match self.shape {
-            Shape::J | Shape::L | Shape::S | Shape::T | Shape::Z => match self.rotation_state {
-                0 => [
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(-1, -1),
-                    BlockPos::new(0, 2),
-                    BlockPos::new(-1, 2),
-                ],
-                1 => [
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(-1, 1),
-                    BlockPos::new(0, -2),
-                    BlockPos::new(-1, -2),
-                ],
-                2 => [
-                    BlockPos::new(1, 0),
-                    BlockPos::new(1, -1),
-                    BlockPos::new(0, 2),
-                    BlockPos::new(1, 2),
-                ],
-                3 => [
-                    BlockPos::new(1, 0),
-                    BlockPos::new(1, 1),
-                    BlockPos::new(0, -2),
-                    BlockPos::new(1, -2),
-                ],
-                _ => panic!(),
-            },
-            Shape::I => match self.rotation_state {
-                0 => [
-                    BlockPos::new(1, 0),
-                    BlockPos::new(-2, 0),
-                    BlockPos::new(1, -2),
-                    BlockPos::new(-2, 1),
-                ],
-                1 => [
-                    BlockPos::new(-2, 0),
-                    BlockPos::new(1, 0),
-                    BlockPos::new(-2, -1),
-                    BlockPos::new(1, 2),
-                ],
-                2 => [
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(2, 0),
-                    BlockPos::new(-1, 2),
-                    BlockPos::new(2, -1),
-                ],
-                3 => [
-                    BlockPos::new(2, 0),
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(2, 1),
-                    BlockPos::new(-1, -2),
-                ],
-                _ => panic!(),
-            },
-            Shape::O => [
-                BlockPos::new(0, 0),
-                BlockPos::new(0, 0),
-                BlockPos::new(0, 0),
-                BlockPos::new(0, 0),
-            ],
-        } {
-            if !self.translate(i, game) {
-                return false;
+        if system.may_kick(self.shape, &obstructed) {
+            for kick in system.kicks(self.shape, from, rotation) {
+                if !self.translate(*kick, game) {
+                    self.kick = *kick;
+                    return false;
+                }
             }
         }
-        self.rotation_state += 3;
-        self.rotation_state %= 4;
+        self.rotation_state = from;
         true
     }
 
+    /// Attempt to rotate the block clockwise
+    ///
+    /// # Return value
+    ///
+    /// Returns `true` when the rotation was unsuccessful
+    pub fn rotate_cw(&mut self, game: &PlayingField) -> bool {
+        self.rotate(Rotation::Clockwise, game)
+    }
+
     /// Attempt to rotate the block counter-clockwise
     ///
     /// # Return value
     ///
     /// Returns `true` when the rotation was unsuccessful
     pub fn rotate_ccw(&mut self, game: &PlayingField) -> bool {
-        self.rotation_state += 3;
-        self.rotation_state %= 4;
-
-        // test default rotation
-        if !self.obstructed(game) {
-            return false;
-        }
+        self.rotate(Rotation::CounterClockwise, game)
+    }
 
-        for i in match self.shape {
-            Shape::J | Shape::L | Shape::S | Shape::T | Shape::Z => match self.rotation_state {
-                0 => [
-                    BlockPos::new(1, 0),
-                    BlockPos::new(1, -1),
-                    BlockPos::new(0, 2),
-                    BlockPos::new(1, 2),
-                ],
-                1 => [
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(-1, 1),
-                    BlockPos::new(0, -2),
-                    BlockPos::new(-1, -2),
-                ],
-                2 => [
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(-1, -1),
-                    BlockPos::new(0, 2),
-                    BlockPos::new(-1, 2),
-                ],
-                3 => [
-                    BlockPos::new(1, 0),
-                    BlockPos::new(1, 1),
-                    BlockPos::new(0, -2),
-                    BlockPos::new(1, -2),
-                ],
-                _ => panic!(),
-            },
-            Shape::I => match self.rotation_state {
-                0 => [
-                    BlockPos::new(2, 0),
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(2, 1),
-                    BlockPos::new(-1, -2),
-                ],
-                1 => [
-                    BlockPos::new(1, 0),
-                    BlockPos::new(-2, 0),
-                    BlockPos::new(1, -2),
-                    BlockPos::new(-2, 1),
-                ],
-                2 => [
-                    BlockPos::new(-2, 0),
-                    BlockPos::new(1, 0),
-                    BlockPos::new(-2, 1),
-                    BlockPos::new(1, 2),
-                ],
-                3 => [
-                    BlockPos::new(-1, 0),
-                    BlockPos::new(2, 0),
-                    BlockPos::new(-1, 2),
-                    BlockPos::new(2, -1),
-                ],
-                _ => panic!(),
-            },
-            Shape::O => [
-                BlockPos::new(0, 0),
-                BlockPos::new(0, 0),
-                BlockPos::new(0, 0),
-                BlockPos::new(0, 0),
-            ],
-        } {
-            if !self.translate(i, game) {
-                return false;
-            }
-        }
-        self.rotation_state += 1;
-        self.rotation_state %= 4;
-        true
+    /// Attempt to turn the block around
+    ///
+    /// # Return value
+    ///
+    /// Returns `true` when the rotation was unsuccessful
+    pub fn rotate_180(&mut self, game: &PlayingField) -> bool {
+        self.rotate(Rotation::Half, game)
     }
 
     const COLORS: [(f32, f32, f32); 7] = [
@@ -270,336 +207,12 @@ impl Tetromino {
 
     /// Generate the shape of the tetromino out of blocks
     pub fn get_shape(&self) -> [[Block; 4]; 4] {
-        match self.shape {
-            Shape::I => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[0],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            ['#', '#', '#', '#'],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[0],
-                        [
-                            [' ', ' ', '#', ' '],
-                            [' ', ' ', '#', ' '],
-                            [' ', ' ', '#', ' '],
-                            [' ', ' ', '#', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[0],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' '],
-                            ['#', '#', '#', '#'],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[0],
-                        [
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
-            Shape::J => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[1],
-                        [
-                            ['#', ' ', ' ', ' '],
-                            ['#', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[1],
-                        [
-                            [' ', '#', '#', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[1],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            ['#', '#', '#', ' '],
-                            [' ', ' ', '#', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[1],
-                        [
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            ['#', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
-            Shape::L => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[2],
-                        [
-                            [' ', ' ', '#', ' '],
-                            ['#', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[2],
-                        [
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[2],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            ['#', '#', '#', ' '],
-                            ['#', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[2],
-                        [
-                            ['#', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
-            Shape::O => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[3],
-                        [
-                            [' ', '#', '#', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[3],
-                        [
-                            [' ', '#', '#', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[3],
-                        [
-                            [' ', '#', '#', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[3],
-                        [
-                            [' ', '#', '#', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
-            Shape::S => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[4],
-                        [
-                            [' ', '#', '#', ' '],
-                            ['#', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[4],
-                        [
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', '#', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[4],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            [' ', '#', '#', ' '],
-                            ['#', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[4],
-                        [
-                            ['#', ' ', ' ', ' '],
-                            ['#', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
-            Shape::T => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[5],
-                        [
-                            [' ', '#', ' ', ' '],
-                            ['#', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[5],
-                        [
-                            [' ', '#', ' ', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[5],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            ['#', '#', '#', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[5],
-                        [
-                            [' ', '#', ' ', ' '],
-                            ['#', '#', ' ', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
-            Shape::Z => match self.rotation_state {
-                0 => {
-                    block_shape!(
-                        Self::COLORS[6],
-                        [
-                            ['#', '#', ' ', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                1 => {
-                    block_shape!(
-                        Self::COLORS[6],
-                        [
-                            [' ', ' ', '#', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', '#', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                2 => {
-                    block_shape!(
-                        Self::COLORS[6],
-                        [
-                            [' ', ' ', ' ', ' '],
-                            ['#', '#', ' ', ' '],
-                            [' ', '#', '#', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                3 => {
-                    block_shape!(
-                        Self::COLORS[6],
-                        [
-                            [' ', '#', ' ', ' '],
-                            ['#', '#', ' ', ' '],
-                            ['#', ' ', ' ', ' '],
-                            [' ', ' ', ' ', ' ']
-                        ]
-                    )
-                }
-                _ => panic!(),
-            },
+        let mut data = [[Block::Air; 4]; 4];
+        for block in self.system.blocks(self.shape, self.rotation_state) {
+            data[block.x as usize][block.y as usize] = Block::Block {
+                color: self.shape.color(),
+            };
         }
+        data
     }
 }
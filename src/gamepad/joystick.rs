@@ -0,0 +1,240 @@
+//! Gamepads read through the Linux joystick interface, `/dev/input/js*`
+
+use super::{Axis, Button, DeviceId, Event};
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// How often new devices are looked for
+const SCAN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// `O_NONBLOCK`, reading returns `WouldBlock` instead of waiting for an event
+const NONBLOCK: i32 = 0o4000;
+
+const EVENT_SIZE: usize = 8;
+const EVENT_BUTTON: u8 = 0x01;
+const EVENT_AXIS: u8 = 0x02;
+/// Set on the made up events describing the state of the device when it's opened
+const EVENT_INIT: u8 = 0x80;
+
+/// The button numbers of the Linux gamepad driver (xpad), other gamepads usually follow it
+const BUTTONS: [Option<Button>; 11] = [
+    Some(Button::South),
+    Some(Button::East),
+    Some(Button::West),
+    Some(Button::North),
+    Some(Button::LeftBumper),
+    Some(Button::RightBumper),
+    Some(Button::Select),
+    Some(Button::Start),
+    // the guide button is left to the system
+    None,
+    Some(Button::LeftStick),
+    Some(Button::RightStick),
+];
+
+const AXIS_LEFT_X: u8 = 0;
+const AXIS_LEFT_Y: u8 = 1;
+/// The d-pad is reported as a pair of axes which are either -1, 0 or 1
+const AXIS_DPAD_X: u8 = 6;
+const AXIS_DPAD_Y: u8 = 7;
+
+struct Joystick {
+    id: DeviceId,
+    path: PathBuf,
+    file: std::fs::File,
+    /// The last value of the d-pad axes, to know which button has been released
+    dpad: (i16, i16),
+}
+
+pub struct Joysticks {
+    joysticks: Vec<Joystick>,
+    next_id: DeviceId,
+    last_scan: Option<std::time::Instant>,
+    /// Devices which couldn't be opened, so that the error is only shown once
+    failed: Vec<PathBuf>,
+}
+
+impl Joysticks {
+    pub fn new() -> Self {
+        Self {
+            joysticks: Vec::new(),
+            next_id: 0,
+            last_scan: None,
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn connected(&self) -> Vec<Event> {
+        self.joysticks
+            .iter()
+            .map(|x| Event::Connected {
+                device: x.id,
+                name: device_name(&x.path),
+            })
+            .collect()
+    }
+
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.last_scan.is_none_or(|x| x.elapsed() >= SCAN_INTERVAL) {
+            self.last_scan = Some(std::time::Instant::now());
+            self.scan(&mut events);
+        }
+
+        let mut buffer = [0; EVENT_SIZE * 64];
+        self.joysticks.retain_mut(|joystick| loop {
+            match joystick.file.read(&mut buffer) {
+                Ok(0) => return true,
+                Ok(n) => {
+                    for data in buffer[..n].chunks_exact(EVENT_SIZE) {
+                        joystick.decode(data, &mut events);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return true,
+                Err(_) => {
+                    // the device has been unplugged
+                    events.push(Event::Disconnected(joystick.id));
+                    return false;
+                }
+            }
+        });
+        events
+    }
+
+    /// Open the devices which have been plugged in since the last scan
+    fn scan(&mut self, events: &mut Vec<Event>) {
+        let entries = match std::fs::read_dir("/dev/input") {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_joystick = path
+                .file_name()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| x.starts_with("js"));
+            if !is_joystick
+                || self.joysticks.iter().any(|x| x.path == path)
+                || self.failed.contains(&path)
+            {
+                continue;
+            }
+            match std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(NONBLOCK)
+                .open(&path)
+            {
+                Ok(file) => {
+                    events.push(Event::Connected {
+                        device: self.next_id,
+                        name: device_name(&path),
+                    });
+                    self.joysticks.push(Joystick {
+                        id: self.next_id,
+                        path,
+                        file,
+                        dpad: (0, 0),
+                    });
+                    self.next_id += 1;
+                }
+                Err(e) => {
+                    eprintln!("Unable to open gamepad {}: {}", path.display(), e);
+                    self.failed.push(path);
+                }
+            }
+        }
+    }
+}
+
+impl Joystick {
+    /// Turn a single `js_event` into our events
+    fn decode(&mut self, data: &[u8], events: &mut Vec<Event>) {
+        let value = i16::from_le_bytes([data[4], data[5]]);
+        let kind = data[6] & !EVENT_INIT;
+        let number = data[7];
+        let device = self.id;
+
+        if kind == EVENT_BUTTON {
+            if let Some(Some(button)) = BUTTONS.get(number as usize) {
+                events.push(Event::Button {
+                    device,
+                    button: *button,
+                    pressed: value != 0,
+                });
+            }
+        } else if kind == EVENT_AXIS {
+            let axis = match number {
+                AXIS_LEFT_X => Axis::LeftStickX,
+                AXIS_LEFT_Y => Axis::LeftStickY,
+                AXIS_DPAD_X => {
+                    let old = std::mem::replace(&mut self.dpad.0, value.signum());
+                    dpad(
+                        device,
+                        old,
+                        value.signum(),
+                        Button::DPadLeft,
+                        Button::DPadRight,
+                        events,
+                    );
+                    return;
+                }
+                AXIS_DPAD_Y => {
+                    let old = std::mem::replace(&mut self.dpad.1, value.signum());
+                    dpad(
+                        device,
+                        old,
+                        value.signum(),
+                        Button::DPadUp,
+                        Button::DPadDown,
+                        events,
+                    );
+                    return;
+                }
+                _ => return,
+            };
+            events.push(Event::Axis {
+                device,
+                axis,
+                value: value as f64 / i16::MAX as f64,
+            });
+        }
+    }
+}
+
+/// Turn a change of a d-pad axis into button presses
+fn dpad(
+    device: DeviceId,
+    old: i16,
+    new: i16,
+    negative: Button,
+    positive: Button,
+    events: &mut Vec<Event>,
+) {
+    if old == new {
+        return;
+    }
+    let button = |x: i16| if x < 0 { negative } else { positive };
+    if old != 0 {
+        events.push(Event::Button {
+            device,
+            button: button(old),
+            pressed: false,
+        });
+    }
+    if new != 0 {
+        events.push(Event::Button {
+            device,
+            button: button(new),
+            pressed: true,
+        });
+    }
+}
+
+/// The name of the device given by the driver
+fn device_name(path: &std::path::Path) -> String {
+    let file = path.file_name().and_then(|x| x.to_str()).unwrap_or("js");
+    std::fs::read_to_string(format!("/sys/class/input/{}/device/name", file))
+        .map(|x| String::from(x.trim()))
+        .unwrap_or_else(|_| String::from(file))
+}
@@ -0,0 +1,422 @@
+#[cfg(target_os = "linux")]
+mod joystick;
+
+use crate::game::Key;
+use glutin::event::VirtualKeyCode;
+
+/// Identifies a connected gamepad. A gamepad which is plugged in again gets a new id.
+pub type DeviceId = u32;
+
+/// The buttons of a gamepad, named after their position like on an Xbox controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    /// A
+    South,
+    /// B
+    East,
+    /// X
+    West,
+    /// Y
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl Button {
+    pub const ALL: [Button; 14] = [
+        Button::South,
+        Button::East,
+        Button::West,
+        Button::North,
+        Button::LeftBumper,
+        Button::RightBumper,
+        Button::Select,
+        Button::Start,
+        Button::LeftStick,
+        Button::RightStick,
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+    ];
+
+    /// The name of the button as stored in files
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::South => "south",
+            Button::East => "east",
+            Button::West => "west",
+            Button::North => "north",
+            Button::LeftBumper => "left_bumper",
+            Button::RightBumper => "right_bumper",
+            Button::Select => "select",
+            Button::Start => "start",
+            Button::LeftStick => "left_stick",
+            Button::RightStick => "right_stick",
+            Button::DPadUp => "dpad_up",
+            Button::DPadDown => "dpad_down",
+            Button::DPadLeft => "dpad_left",
+            Button::DPadRight => "dpad_right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.name() == name)
+    }
+}
+
+/// The analog axes used by the game. Both go from -1 to 1, positive is right and down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    LeftStickX,
+    LeftStickY,
+}
+
+/// Something that happened on a gamepad. The events don't depend on the platform, so they can
+/// also be made up, like when testing the game without a controller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Connected {
+        device: DeviceId,
+        name: String,
+    },
+    Disconnected(DeviceId),
+    Button {
+        device: DeviceId,
+        button: Button,
+        pressed: bool,
+    },
+    Axis {
+        device: DeviceId,
+        axis: Axis,
+        value: f64,
+    },
+}
+
+impl Event {
+    pub fn device(&self) -> DeviceId {
+        match self {
+            Event::Connected { device, .. }
+            | Event::Button { device, .. }
+            | Event::Axis { device, .. } => *device,
+            Event::Disconnected(device) => *device,
+        }
+    }
+
+    /// The keyboard key which does the same in the menus, so that they can be used with a
+    /// gamepad only
+    pub fn menu_key(&self) -> Option<VirtualKeyCode> {
+        match self {
+            Event::Button {
+                button,
+                pressed: true,
+                ..
+            } => match button {
+                Button::DPadUp => Some(VirtualKeyCode::Up),
+                Button::DPadDown => Some(VirtualKeyCode::Down),
+                Button::DPadLeft => Some(VirtualKeyCode::Left),
+                Button::DPadRight => Some(VirtualKeyCode::Right),
+                Button::South | Button::Start => Some(VirtualKeyCode::Return),
+                Button::East | Button::Select => Some(VirtualKeyCode::Escape),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// What the buttons and the left stick of every gamepad do
+#[derive(Clone)]
+pub struct GamepadBinds {
+    buttons: Vec<(Button, Key)>,
+    /// How far the stick has to be tilted before it does anything, from 0 to 1
+    pub deadzone: f64,
+}
+
+impl Default for GamepadBinds {
+    fn default() -> Self {
+        Self {
+            buttons: vec![
+                (Button::South, Key::RotateCCW),
+                (Button::East, Key::RotateCW),
+                (Button::West, Key::Rotate180),
+                (Button::North, Key::Swap),
+                (Button::LeftBumper, Key::Swap),
+                (Button::RightBumper, Key::Target),
+                (Button::Select, Key::Restart),
+                (Button::Start, Key::Pause),
+                (Button::DPadUp, Key::HardDrop),
+                (Button::DPadDown, Key::SoftDrop),
+                (Button::DPadLeft, Key::Left),
+                (Button::DPadRight, Key::Right),
+            ],
+            deadzone: 0.3,
+        }
+    }
+}
+
+impl GamepadBinds {
+    pub fn decode(&self, button: Button) -> Option<Key> {
+        self.buttons
+            .iter()
+            .find(|(x, _)| *x == button)
+            .map(|(_, key)| *key)
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut buttons = json::JsonValue::new_object();
+        for (button, key) in &self.buttons {
+            buttons[button.name()] = key.name().into();
+        }
+        json::object! {
+            buttons: buttons,
+            deadzone: self.deadzone,
+        }
+    }
+
+    /// Read the binds written by [`GamepadBinds::to_json`], anything missing is left at the
+    /// default. Buttons bound to `null` don't do anything.
+    pub fn from_json(data: &json::JsonValue) -> Self {
+        let mut binds = Self::default();
+        for (name, key) in data["buttons"].entries() {
+            let button = match Button::from_name(name) {
+                Some(x) => x,
+                None => {
+                    eprintln!("Unknown gamepad button {}", name);
+                    continue;
+                }
+            };
+            binds.buttons.retain(|(x, _)| *x != button);
+            if key.is_null() {
+                continue;
+            }
+            match key.as_str().and_then(Key::from_name) {
+                Some(key) => binds.buttons.push((button, key)),
+                None => eprintln!("Unknown action {} bound to {}", key, name),
+            }
+        }
+        if let Some(x) = data["deadzone"].as_f64() {
+            binds.deadzone = x.clamp(0.0, 0.95);
+        }
+        binds
+    }
+}
+
+/// A gamepad playing on a board. Turns the events of the gamepad into the keys of the board.
+pub struct Controller {
+    pub device: DeviceId,
+    binds: GamepadBinds,
+    /// The buttons being held down
+    buttons: Vec<Button>,
+    /// The keys held by tilting the stick
+    stick: Vec<Key>,
+}
+
+impl Controller {
+    pub fn new(device: DeviceId, binds: GamepadBinds) -> Self {
+        Self {
+            device,
+            binds,
+            buttons: Vec::new(),
+            stick: Vec::new(),
+        }
+    }
+
+    /// The keys held by the buttons and the stick, a key held by several of them only once
+    fn keys(&self) -> Vec<Key> {
+        let mut keys = self.stick.clone();
+        keys.extend(self.buttons.iter().filter_map(|x| self.binds.decode(*x)));
+        keys.sort_by_key(|x| x.name());
+        keys.dedup();
+        keys
+    }
+
+    /// The keys pressed and released by an event of this controller's gamepad. A key bound to
+    /// several buttons is released once none of them is held anymore.
+    pub fn translate(&mut self, event: &Event) -> Vec<(Key, bool)> {
+        let before = self.keys();
+        match *event {
+            Event::Button {
+                button, pressed, ..
+            } => {
+                self.buttons.retain(|x| *x != button);
+                if pressed {
+                    self.buttons.push(button);
+                }
+            }
+            Event::Axis { axis, value, .. } => {
+                let deadzone = self.binds.deadzone;
+                // a tilted axis holds one of its two keys, or none in the middle
+                let (negative, positive) = match axis {
+                    Axis::LeftStickX => (Some(Key::Left), Some(Key::Right)),
+                    Axis::LeftStickY => (None, Some(Key::SoftDrop)),
+                };
+                let held = if value < -deadzone {
+                    negative
+                } else if value > deadzone {
+                    positive
+                } else {
+                    None
+                };
+                for key in [negative, positive].into_iter().flatten() {
+                    self.stick.retain(|x| *x != key);
+                    if held == Some(key) {
+                        self.stick.push(key);
+                    }
+                }
+            }
+            Event::Connected { .. } | Event::Disconnected(_) => (),
+        }
+        let after = self.keys();
+
+        let released = before.iter().filter(|x| !after.contains(x));
+        let pressed = after.iter().filter(|x| !before.contains(x));
+        released
+            .map(|x| (*x, false))
+            .chain(pressed.map(|x| (*x, true)))
+            .collect()
+    }
+
+    /// Let go of everything, when the gamepad is unplugged
+    pub fn release_all(&mut self) -> Vec<(Key, bool)> {
+        let keys = self.keys();
+        self.stick.clear();
+        self.buttons.clear();
+        keys.into_iter().map(|x| (x, false)).collect()
+    }
+}
+
+/// Finds the gamepads connected to the computer and reads their events
+pub struct Gamepads {
+    #[cfg(target_os = "linux")]
+    joysticks: joystick::Joysticks,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            joysticks: joystick::Joysticks::new(),
+        }
+    }
+
+    /// Look for new gamepads and take the events which happened since the last call
+    pub fn poll(&mut self) -> Vec<Event> {
+        #[cfg(target_os = "linux")]
+        return self.joysticks.poll();
+        #[cfg(not(target_os = "linux"))]
+        return Vec::new();
+    }
+
+    /// A [`Event::Connected`] for every gamepad, so that a new screen learns about them
+    pub fn connected(&self) -> Vec<Event> {
+        #[cfg(target_os = "linux")]
+        return self.joysticks.connected();
+        #[cfg(not(target_os = "linux"))]
+        return Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn button(button: Button, pressed: bool) -> Event {
+        Event::Button {
+            device: 0,
+            button,
+            pressed,
+        }
+    }
+
+    fn stick(axis: Axis, value: f64) -> Event {
+        Event::Axis {
+            device: 0,
+            axis,
+            value,
+        }
+    }
+
+    #[test]
+    fn buttons_press_their_keys() {
+        let mut controller = Controller::new(0, GamepadBinds::default());
+        assert_eq!(
+            controller.translate(&button(Button::South, true)),
+            [(Key::RotateCCW, true)]
+        );
+        assert_eq!(
+            controller.translate(&button(Button::South, false)),
+            [(Key::RotateCCW, false)]
+        );
+        assert!(controller
+            .translate(&button(Button::LeftStick, true))
+            .is_empty());
+    }
+
+    #[test]
+    fn stick_has_a_deadzone() {
+        let mut controller = Controller::new(0, GamepadBinds::default());
+        assert!(controller
+            .translate(&stick(Axis::LeftStickX, -0.2))
+            .is_empty());
+        assert_eq!(
+            controller.translate(&stick(Axis::LeftStickX, -0.8)),
+            [(Key::Left, true)]
+        );
+        assert!(controller
+            .translate(&stick(Axis::LeftStickX, -1.0))
+            .is_empty());
+        // flicking to the other side lets go of the first key
+        assert_eq!(
+            controller.translate(&stick(Axis::LeftStickX, 0.8)),
+            [(Key::Left, false), (Key::Right, true)]
+        );
+        assert!(controller
+            .translate(&stick(Axis::LeftStickY, -0.8))
+            .is_empty());
+        assert_eq!(
+            controller.translate(&stick(Axis::LeftStickY, 0.8)),
+            [(Key::SoftDrop, true)]
+        );
+    }
+
+    #[test]
+    fn buttons_bound_to_the_same_key_hold_it_together() {
+        let mut controller = Controller::new(0, GamepadBinds::default());
+        assert_eq!(
+            controller.translate(&button(Button::North, true)),
+            [(Key::Swap, true)]
+        );
+        assert!(controller
+            .translate(&button(Button::LeftBumper, true))
+            .is_empty());
+        assert!(controller
+            .translate(&button(Button::North, false))
+            .is_empty());
+        assert_eq!(
+            controller.translate(&button(Button::LeftBumper, false)),
+            [(Key::Swap, false)]
+        );
+    }
+
+    #[test]
+    fn unplugging_releases_everything() {
+        let mut controller = Controller::new(0, GamepadBinds::default());
+        controller.translate(&button(Button::North, true));
+        controller.translate(&button(Button::LeftBumper, true));
+        controller.translate(&stick(Axis::LeftStickX, 1.0));
+        assert_eq!(
+            controller.release_all(),
+            [(Key::Right, false), (Key::Swap, false)]
+        );
+        assert!(controller.release_all().is_empty());
+    }
+}
@@ -1,8 +1,12 @@
 mod graphics;
 
 mod game;
+mod gamepad;
 mod menu;
+mod net;
+mod records;
 mod resource;
+mod settings;
 mod text;
 mod util;
 
@@ -53,6 +57,12 @@ fn main() {
     let roman = resource::ResourceManager::new(String::from("resources")).unwrap();
     let tr = Rc::new(text::TextRenderer::new(&mut gh, &roman).unwrap());
     let mut screen: Box<dyn Playable> = Box::new(menu::Menu::new(&mut gh, &roman, tr.clone()));
+    let mut gamepads = gamepad::Gamepads::new();
+
+    // the screens are updated at a fixed rate, no matter how fast we are drawing
+    let tick = std::time::Duration::from_secs(1) / TICKS_PER_SECOND;
+    let mut last_frame = std::time::Instant::now();
+    let mut lag = std::time::Duration::ZERO;
 
     evloop.run(move |ev, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -64,7 +74,17 @@ fn main() {
                 _ => (),
             },
             Event::MainEventsCleared => {
-                screen.update();
+                let now = std::time::Instant::now();
+                // don't try to catch up after the window has been frozen for a while
+                lag = (lag + now.duration_since(last_frame)).min(tick * TICKS_PER_SECOND / 4);
+                last_frame = now;
+                for event in gamepads.poll() {
+                    screen.gamepad(event);
+                }
+                while lag >= tick {
+                    screen.update();
+                    lag -= tick;
+                }
 
                 let winsize = context.window().inner_size();
                 unsafe {
@@ -82,29 +102,93 @@ fn main() {
                         Screen::Menu => {
                             screen = Box::new(menu::Menu::new(&mut gh, &roman, tr.clone()))
                         }
-                        Screen::SingleGame => {
+                        Screen::SingleMenu => {
+                            screen = Box::new(menu::Menu::single(&mut gh, &roman, tr.clone()))
+                        }
+                        Screen::SingleGame(goal) => {
                             screen = Box::new(Game::new(
                                 &mut gh,
                                 &roman,
                                 tr.clone(),
-                                game::GameMode::Single,
+                                game::GameMode::Single(goal),
                                 audio.clone(),
                             ))
                         }
-                        Screen::DoubleGame => {
+                        Screen::SplitMenu => {
+                            screen = Box::new(menu::Menu::split(&mut gh, &roman, tr.clone()))
+                        }
+                        Screen::SplitGame(n) => {
                             screen = Box::new(Game::new(
                                 &mut gh,
                                 &roman,
                                 tr.clone(),
-                                game::GameMode::Double,
+                                game::GameMode::Split(n),
+                                audio.clone(),
+                            ))
+                        }
+                        Screen::Rematch(series) => {
+                            screen = Box::new(Game::rematch(
+                                &mut gh,
+                                &roman,
+                                tr.clone(),
+                                series,
                                 audio.clone(),
                             ))
                         }
+                        Screen::OnlineGame => {
+                            match Game::online(&mut gh, &roman, tr.clone(), audio.clone()) {
+                                Ok(x) => screen = Box::new(x),
+                                Err(e) => {
+                                    eprintln!(
+                                        "Unable to connect to the relay at {}: {}",
+                                        net::relay_address(),
+                                        e
+                                    );
+                                    screen = Box::new(menu::Menu::new(&mut gh, &roman, tr.clone()))
+                                }
+                            }
+                        }
+                        Screen::Settings => {
+                            screen = Box::new(menu::SettingsMenu::new(&mut gh, &roman, tr.clone()))
+                        }
+                        Screen::Replays => {
+                            screen = Box::new(menu::Menu::replays(&mut gh, &roman, tr.clone()))
+                        }
+                        Screen::Records => {
+                            screen = Box::new(menu::Menu::records(&mut gh, &roman, tr.clone()))
+                        }
+                        Screen::Replay(file) => match game::Replay::load(&file) {
+                            Ok(replay) => {
+                                screen = Box::new(Game::from_replay(
+                                    &mut gh,
+                                    &roman,
+                                    tr.clone(),
+                                    replay,
+                                    audio.clone(),
+                                ))
+                            }
+                            Err(e) => {
+                                eprintln!("Unable to load replay {}: {}", file.display(), e);
+                                screen = Box::new(menu::Menu::replays(&mut gh, &roman, tr.clone()))
+                            }
+                        },
+                        Screen::Results(results) => {
+                            screen = Box::new(menu::ResultsMenu::new(
+                                &mut gh,
+                                &roman,
+                                tr.clone(),
+                                results,
+                            ))
+                        }
                         Screen::Exit => {
                             *control_flow = ControlFlow::Exit;
                             return;
                         }
                     };
+                    // the new screen doesn't know which gamepads are there yet
+                    for event in gamepads.connected() {
+                        screen.gamepad(event);
+                    }
                 }
                 context.swap_buffers().unwrap();
             }
@@ -3,6 +3,12 @@ use crate::text;
 use glam::{Mat4, Vec3, Vec4};
 use std::rc::Rc;
 
+mod results;
+mod settings;
+
+pub use results::ResultsMenu;
+pub use settings::SettingsMenu;
+
 struct MenuItem {
     target: Screen,
     zoom: f32,
@@ -33,6 +39,8 @@ pub struct Menu {
     active_item: usize,
     items: Vec<MenuItem>,
     chosen: Option<crate::Screen>,
+    /// Where to go when escape is pressed
+    back: Option<crate::Screen>,
     spring_position: f32,
     tr: Rc<text::TextRenderer>,
     font: text::Font,
@@ -43,6 +51,266 @@ impl Menu {
         gh: &mut graphics::GraphicsHandle,
         roman: &crate::resource::ResourceManager,
         tr: Rc<text::TextRenderer>,
+    ) -> Self {
+        let items = vec![
+            MenuItem::new(
+                Screen::SingleMenu,
+                String::from("single player"),
+                graphics::Texture::from_image(gh, &roman.get_image("single.png")).unwrap(),
+                Vec4::new(0.1, 0.6, 0.9, 1.0),
+            ),
+            MenuItem::new(
+                Screen::SplitMenu,
+                String::from("split screen"),
+                graphics::Texture::from_image(gh, &roman.get_image("double.png")).unwrap(),
+                Vec4::new(1.0, 0.0, 1.0, 1.0),
+            ),
+            MenuItem::new(
+                Screen::OnlineGame,
+                String::from("online"),
+                graphics::Texture::from_image(gh, &roman.get_image("online.png")).unwrap(),
+                Vec4::new(1.0, 0.6, 0.0, 1.0),
+            ),
+            MenuItem::new(
+                Screen::Settings,
+                String::from("settings"),
+                graphics::Texture::from_image(gh, &roman.get_image("settings.png")).unwrap(),
+                Vec4::new(0.9, 0.7, 0.1, 1.0),
+            ),
+            MenuItem::new(
+                Screen::Replays,
+                String::from("replays"),
+                graphics::Texture::from_image(gh, &roman.get_image("replay.png")).unwrap(),
+                Vec4::new(0.2, 0.8, 0.3, 1.0),
+            ),
+            MenuItem::new(
+                Screen::Records,
+                String::from("records"),
+                graphics::Texture::from_image(gh, &roman.get_image("star.png")).unwrap(),
+                Vec4::new(0.9, 0.7, 0.1, 1.0),
+            ),
+            MenuItem::new(
+                Screen::Exit,
+                String::from("exit to desktop"),
+                graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
+                Vec4::new(1.0, 0.15, 0.1, 1.0),
+            ),
+        ];
+        Self::with_items(gh, roman, tr, items, None)
+    }
+
+    /// The list of saved replays, the newest ones first
+    pub fn replays(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+    ) -> Self {
+        use crate::game::GameMode;
+
+        const REPLAYS: usize = 50;
+
+        let files = crate::game::Replay::list();
+        let mut items = Vec::new();
+        for file in files.iter().take(REPLAYS) {
+            let name = file
+                .file_stem()
+                .map_or(String::new(), |x| x.to_string_lossy().replace('_', " "));
+            // replays which can't be played stay in the list, choosing them does nothing
+            let (target, text, icon, color) = match crate::game::Replay::info(file) {
+                Ok(info) if info.playable() => {
+                    let (icon, color) = match info.mode {
+                        Some(GameMode::Split(_)) => ("double.png", Vec4::new(1.0, 0.0, 1.0, 1.0)),
+                        Some(GameMode::Online) => ("online.png", Vec4::new(1.0, 0.6, 0.0, 1.0)),
+                        _ => ("single.png", Vec4::new(0.1, 0.6, 0.9, 1.0)),
+                    };
+                    let seconds = info.ticks / crate::TICKS_PER_SECOND as u64;
+                    (
+                        Screen::Replay(file.clone()),
+                        format!("{}  {}:{:02}", name, seconds / 60, seconds % 60),
+                        icon,
+                        color,
+                    )
+                }
+                Ok(info) => (
+                    Screen::Replays,
+                    format!("{}  old version {}", name, info.version),
+                    "replay.png",
+                    Vec4::new(0.4, 0.4, 0.4, 1.0),
+                ),
+                Err(_) => (
+                    Screen::Replays,
+                    format!("{}  unreadable", name),
+                    "replay.png",
+                    Vec4::new(0.4, 0.4, 0.4, 1.0),
+                ),
+            };
+            items.push(MenuItem::new(
+                target,
+                text,
+                graphics::Texture::from_image(gh, &roman.get_image(icon)).unwrap(),
+                color,
+            ));
+        }
+        if files.len() > REPLAYS {
+            items.push(MenuItem::new(
+                Screen::Replays,
+                format!("{} older replays", files.len() - REPLAYS),
+                graphics::Texture::from_image(gh, &roman.get_image("replay.png")).unwrap(),
+                Vec4::new(0.4, 0.4, 0.4, 1.0),
+            ));
+        }
+        items.push(MenuItem::new(
+            Screen::Menu,
+            String::from("back"),
+            graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
+            Vec4::new(1.0, 0.15, 0.1, 1.0),
+        ));
+        Self::with_items(gh, roman, tr, items, Some(Screen::Menu))
+    }
+
+    /// The leaderboard of every goal, the split screen matches and the last games played
+    pub fn records(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+    ) -> Self {
+        use crate::game::{GameMode, Goal};
+
+        const LEADERBOARD: usize = 5;
+        const HISTORY: usize = 20;
+
+        let records = crate::records::Records::load();
+        let mut items = Vec::new();
+        let mut add = |text: String, icon: &str, color: Vec4| {
+            items.push(MenuItem::new(
+                Screen::Records,
+                text,
+                graphics::Texture::from_image(gh, &roman.get_image(icon)).unwrap(),
+                color,
+            ));
+        };
+        // the date without the time of the day
+        let day = |x: &str| String::from(x.split('_').next().unwrap_or(x));
+
+        for goal in Goal::ALL {
+            let leaderboard = records.leaderboard(GameMode::Single(goal));
+            if leaderboard.is_empty() {
+                continue;
+            }
+            add(
+                String::from(goal.title()),
+                "star.png",
+                Vec4::new(0.9, 0.7, 0.1, 1.0),
+            );
+            for (i, x) in leaderboard.iter().take(LEADERBOARD).enumerate() {
+                add(
+                    format!("{}. {}  {}  {}", i + 1, x.player, x.result(), day(&x.date)),
+                    "single.png",
+                    Vec4::new(0.1, 0.6, 0.9, 1.0),
+                );
+            }
+        }
+        if !records.versus.is_empty() {
+            add(
+                String::from("split screen"),
+                "star.png",
+                Vec4::new(0.9, 0.7, 0.1, 1.0),
+            );
+            for x in &records.versus {
+                add(
+                    format!("{}  {} won  {} lost", x.player, x.wins, x.losses),
+                    "double.png",
+                    Vec4::new(1.0, 0.0, 1.0, 1.0),
+                );
+            }
+        }
+        if !records.games.is_empty() {
+            add(
+                String::from("history"),
+                "star.png",
+                Vec4::new(0.9, 0.7, 0.1, 1.0),
+            );
+            for x in records.games.iter().rev().take(HISTORY) {
+                add(
+                    format!(
+                        "{}  {}  {}  {}  {:.2} pps",
+                        day(&x.date),
+                        x.player,
+                        x.mode.name(),
+                        x.result(),
+                        x.pps
+                    ),
+                    "replay.png",
+                    Vec4::new(0.2, 0.8, 0.3, 1.0),
+                );
+            }
+        }
+        items.push(MenuItem::new(
+            Screen::Menu,
+            String::from("back"),
+            graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
+            Vec4::new(1.0, 0.15, 0.1, 1.0),
+        ));
+        Self::with_items(gh, roman, tr, items, Some(Screen::Menu))
+    }
+
+    /// The goal of a single player game
+    pub fn single(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+    ) -> Self {
+        let mut items = crate::game::Goal::ALL
+            .iter()
+            .map(|goal| {
+                MenuItem::new(
+                    Screen::SingleGame(*goal),
+                    String::from(goal.title()),
+                    graphics::Texture::from_image(gh, &roman.get_image("single.png")).unwrap(),
+                    Vec4::new(0.1, 0.6, 0.9, 1.0),
+                )
+            })
+            .collect::<Vec<_>>();
+        items.push(MenuItem::new(
+            Screen::Menu,
+            String::from("back"),
+            graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
+            Vec4::new(1.0, 0.15, 0.1, 1.0),
+        ));
+        Self::with_items(gh, roman, tr, items, Some(Screen::Menu))
+    }
+
+    /// The number of players sharing the keyboard
+    pub fn split(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+    ) -> Self {
+        let mut items = (2..=crate::game::MAX_PLAYERS)
+            .map(|n| {
+                MenuItem::new(
+                    Screen::SplitGame(n),
+                    format!("{} players", n),
+                    graphics::Texture::from_image(gh, &roman.get_image("double.png")).unwrap(),
+                    Vec4::new(1.0, 0.0, 1.0, 1.0),
+                )
+            })
+            .collect::<Vec<_>>();
+        items.push(MenuItem::new(
+            Screen::Menu,
+            String::from("back"),
+            graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
+            Vec4::new(1.0, 0.15, 0.1, 1.0),
+        ));
+        Self::with_items(gh, roman, tr, items, Some(Screen::Menu))
+    }
+
+    fn with_items(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+        items: Vec<MenuItem>,
+        back: Option<Screen>,
     ) -> Self {
         let font = roman.get_binary("comfortaa-bold.ttf").clone();
         let font = text::Font::new(&tr, font, 100).unwrap();
@@ -79,26 +347,8 @@ impl Menu {
         Self {
             chosen: None,
             active_item: 0,
-            items: vec![
-                MenuItem::new(
-                    Screen::SingleGame,
-                    String::from("single player"),
-                    graphics::Texture::from_image(gh, &roman.get_image("single.png")).unwrap(),
-                    Vec4::new(0.1, 0.6, 0.9, 1.0),
-                ),
-                MenuItem::new(
-                    Screen::DoubleGame,
-                    String::from("split screen"),
-                    graphics::Texture::from_image(gh, &roman.get_image("double.png")).unwrap(),
-                    Vec4::new(1.0, 0.0, 1.0, 1.0),
-                ),
-                MenuItem::new(
-                    Screen::Exit,
-                    String::from("exit to desktop"),
-                    graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
-                    Vec4::new(1.0, 0.15, 0.1, 1.0),
-                ),
-            ],
+            items,
+            back,
             spring_position: 0.0,
             tr,
             font,
@@ -109,6 +359,29 @@ impl Menu {
             color: Vec4::new(0.0, 0.0, 0.0, 1.0),
         }
     }
+
+    /// Handle a key press, from the keyboard or from a gamepad
+    fn press(&mut self, x: glutin::event::VirtualKeyCode) {
+        match x {
+            glutin::event::VirtualKeyCode::W | glutin::event::VirtualKeyCode::Up => {
+                if self.active_item > 0 {
+                    self.active_item -= 1;
+                }
+            }
+            glutin::event::VirtualKeyCode::S | glutin::event::VirtualKeyCode::Down => {
+                if self.active_item < self.items.len() - 1 {
+                    self.active_item += 1;
+                }
+            }
+            glutin::event::VirtualKeyCode::Space | glutin::event::VirtualKeyCode::Return => {
+                self.chosen = Some(self.items.swap_remove(self.active_item).target);
+            }
+            glutin::event::VirtualKeyCode::Escape => {
+                self.chosen = self.back.take();
+            }
+            _ => (),
+        }
+    }
 }
 
 impl Playable for Menu {
@@ -208,24 +481,14 @@ impl Playable for Menu {
         if let glutin::event::ElementState::Released = event.state {
             return;
         }
-        match event.virtual_keycode {
-            Some(x) => match x {
-                glutin::event::VirtualKeyCode::W | glutin::event::VirtualKeyCode::Up => {
-                    if self.active_item > 0 {
-                        self.active_item -= 1;
-                    }
-                }
-                glutin::event::VirtualKeyCode::S | glutin::event::VirtualKeyCode::Down => {
-                    if self.active_item < self.items.len() - 1 {
-                        self.active_item += 1;
-                    }
-                }
-                glutin::event::VirtualKeyCode::Space | glutin::event::VirtualKeyCode::Return => {
-                    self.chosen = Some(self.items.swap_remove(self.active_item).target);
-                }
-                _ => (),
-            },
-            None => (),
+        if let Some(x) = event.virtual_keycode {
+            self.press(x);
+        }
+    }
+
+    fn gamepad(&mut self, event: crate::gamepad::Event) {
+        if let Some(x) = event.menu_key() {
+            self.press(x);
         }
     }
 
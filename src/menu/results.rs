@@ -0,0 +1,111 @@
+use super::*;
+
+/// The screen shown after a game, with what has been achieved. It looks like the
+/// menu, but only the last two items can be chosen: playing again or going back. The others can
+/// be selected to scroll them into view.
+pub struct ResultsMenu {
+    menu: Menu,
+    results: crate::game::Results,
+    /// The index of the first item which can be chosen
+    first_choice: usize,
+    chosen: Option<Screen>,
+}
+
+impl ResultsMenu {
+    pub fn new(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+        results: crate::game::Results,
+    ) -> Self {
+        let mut items = vec![MenuItem::new(
+            Screen::Menu,
+            results.title.clone(),
+            graphics::Texture::from_image(gh, &roman.get_image("single.png")).unwrap(),
+            Vec4::new(0.9, 0.7, 0.1, 1.0),
+        )];
+        for (name, value) in &results.stats {
+            items.push(MenuItem::new(
+                Screen::Menu,
+                format!("{}  {}", name, value),
+                graphics::Texture::from_image(gh, &roman.get_image("settings.png")).unwrap(),
+                Vec4::new(0.1, 0.6, 0.9, 1.0),
+            ));
+        }
+        let first_choice = items.len();
+        items.push(MenuItem::new(
+            results.again(),
+            String::from(results.again_label()),
+            graphics::Texture::from_image(gh, &roman.get_image("replay.png")).unwrap(),
+            Vec4::new(0.2, 0.8, 0.3, 1.0),
+        ));
+        items.push(MenuItem::new(
+            Screen::Menu,
+            String::from("back to menu"),
+            graphics::Texture::from_image(gh, &roman.get_image("exit.png")).unwrap(),
+            Vec4::new(1.0, 0.15, 0.1, 1.0),
+        ));
+
+        let mut menu = Menu::with_items(gh, roman, tr, items, None);
+        menu.active_item = first_choice;
+        Self {
+            menu,
+            results,
+            first_choice,
+            chosen: None,
+        }
+    }
+
+    /// Handle a key press, from the keyboard or from a gamepad
+    fn press(&mut self, x: glutin::event::VirtualKeyCode) {
+        use glutin::event::VirtualKeyCode;
+
+        let menu = &mut self.menu;
+        match x {
+            VirtualKeyCode::W | VirtualKeyCode::Up => {
+                menu.active_item = menu.active_item.saturating_sub(1);
+            }
+            VirtualKeyCode::S | VirtualKeyCode::Down => {
+                menu.active_item = (menu.active_item + 1).min(menu.items.len() - 1);
+            }
+            VirtualKeyCode::Space | VirtualKeyCode::Return => {
+                if menu.active_item == self.first_choice {
+                    self.chosen = Some(self.results.again());
+                } else if menu.active_item > self.first_choice {
+                    self.chosen = Some(Screen::Menu);
+                }
+            }
+            VirtualKeyCode::Escape => self.chosen = Some(Screen::Menu),
+            _ => (),
+        }
+    }
+}
+
+impl Playable for ResultsMenu {
+    fn update(&mut self) {
+        self.menu.update();
+    }
+
+    fn draw(&mut self, gh: &mut graphics::GraphicsHandle, screen_width: i32, screen_height: i32) {
+        self.menu.draw(gh, screen_width, screen_height);
+    }
+
+    fn input(&mut self, event: glutin::event::KeyboardInput) {
+        if let glutin::event::ElementState::Released = event.state {
+            return;
+        }
+        if let Some(x) = event.virtual_keycode {
+            self.press(x);
+        }
+    }
+
+    fn gamepad(&mut self, event: crate::gamepad::Event) {
+        if let Some(x) = event.menu_key() {
+            self.press(x);
+        }
+    }
+
+    fn next_screen(&mut self) -> Option<Screen> {
+        self.chosen.take()
+    }
+}
@@ -0,0 +1,458 @@
+use super::*;
+use crate::game::{Goal, Key};
+use crate::settings::{Settings, Wallpaper, PROFILES};
+
+/// A value of [`crate::game::Handling`] which can be edited
+#[derive(Clone, Copy)]
+enum Field {
+    Das,
+    Arr,
+    Sdf,
+    Dcd,
+    Gravity,
+    LockDelay,
+    LockResets,
+}
+
+impl Field {
+    const ALL: [Field; 7] = [
+        Field::Das,
+        Field::Arr,
+        Field::Sdf,
+        Field::Dcd,
+        Field::Gravity,
+        Field::LockDelay,
+        Field::LockResets,
+    ];
+
+    fn text(self, handling: &crate::game::Handling) -> String {
+        match self {
+            Field::Das => format!("DAS  {} ms", handling.das),
+            Field::Arr => match handling.arr {
+                0 => String::from("ARR  instant"),
+                x => format!("ARR  {} ms", x),
+            },
+            Field::Sdf => match handling.sdf {
+                Some(x) => format!("SDF  {}×", x),
+                None => String::from("SDF  instant"),
+            },
+            Field::Dcd => format!("DCD  {} ms", handling.dcd),
+            Field::Gravity => format!("gravity  {} ms", handling.gravity),
+            Field::LockDelay => format!("lock delay  {} ms", handling.lock_delay),
+            Field::LockResets => format!("lock resets  {}", handling.lock_resets),
+        }
+    }
+
+    /// Make the value bigger or smaller by `steps` steps
+    fn adjust(self, handling: &mut crate::game::Handling, steps: i64) {
+        let change = |x: u64, step: u64, max: u64| {
+            (x as i64 + steps * step as i64).clamp(0, max as i64) as u64
+        };
+        match self {
+            Field::Das => handling.das = change(handling.das, 10, 1000),
+            Field::Arr => handling.arr = change(handling.arr, 5, 500),
+            Field::Sdf => {
+                // above the biggest factor the piece drops instantly
+                handling.sdf = match handling.sdf {
+                    Some(x) if x as i64 + steps * 5 > 100 => None,
+                    Some(x) => Some(change(x as u64, 5, 100).max(5) as u32),
+                    None if steps < 0 => Some(100),
+                    None => None,
+                }
+            }
+            Field::Dcd => handling.dcd = change(handling.dcd, 10, 1000),
+            Field::Gravity => {
+                handling.gravity =
+                    ((handling.gravity / 50.0).round() + steps as f64).clamp(0.0, 100.0) * 50.0
+            }
+            Field::LockDelay => handling.lock_delay = change(handling.lock_delay, 50, 5000),
+            Field::LockResets => {
+                handling.lock_resets = change(handling.lock_resets as u64, 1, 30) as u32
+            }
+        }
+    }
+}
+
+/// A line of the settings screen
+#[derive(Clone, Copy)]
+enum Row {
+    /// Which profile's handling and keys are being edited
+    Profile,
+    Handling(Field),
+    Key(Key),
+    /// The deadzone of the gamepad sticks
+    Deadzone,
+    Rotation,
+    Ruleset,
+    AllSpin,
+    PerfectClear,
+    /// The size of the playing field
+    Width,
+    Height,
+    /// The randomizer of the single player games of a goal, or of split screen games
+    Randomizer(Option<Goal>),
+    Preview,
+    BestOf,
+    Volume,
+    Particles,
+    BoardMotion,
+    Wallpaper,
+    Back,
+}
+
+/// The screen where the settings are edited. It looks like the menu, with the value of every
+/// setting shown in its item. Left and right change the value, enter starts binding a key and
+/// delete removes all the keys of an action.
+pub struct SettingsMenu {
+    menu: Menu,
+    rows: Vec<Row>,
+    settings: Settings,
+    profile: usize,
+    /// Some when waiting for the key to be bound
+    binding: Option<Key>,
+    /// The choices of the wallpaper
+    wallpapers: Vec<Wallpaper>,
+    /// The names of the rotation systems to choose from
+    rotations: Vec<String>,
+    /// The names of the rulesets to choose from
+    rulesets: Vec<String>,
+    done: bool,
+}
+
+impl SettingsMenu {
+    pub fn new(
+        gh: &mut graphics::GraphicsHandle,
+        roman: &crate::resource::ResourceManager,
+        tr: Rc<text::TextRenderer>,
+    ) -> Self {
+        let mut rows = vec![Row::Profile];
+        rows.extend(Field::ALL.iter().map(|x| Row::Handling(*x)));
+        rows.extend(Key::ALL.iter().map(|x| Row::Key(*x)));
+        rows.extend([
+            Row::Deadzone,
+            Row::Rotation,
+            Row::Ruleset,
+            Row::AllSpin,
+            Row::PerfectClear,
+            Row::Width,
+            Row::Height,
+        ]);
+        rows.extend(Goal::ALL.iter().map(|x| Row::Randomizer(Some(*x))));
+        rows.extend([
+            Row::Randomizer(None),
+            Row::Preview,
+            Row::BestOf,
+            Row::Volume,
+            Row::Particles,
+            Row::BoardMotion,
+            Row::Wallpaper,
+            Row::Back,
+        ]);
+
+        let items = rows
+            .iter()
+            .map(|row| {
+                let (icon, color) = match row {
+                    Row::Profile => ("settings.png", Vec4::new(0.9, 0.7, 0.1, 1.0)),
+                    Row::Handling(_) => ("settings.png", Vec4::new(0.1, 0.6, 0.9, 1.0)),
+                    Row::Key(_) | Row::Deadzone => ("settings.png", Vec4::new(1.0, 0.0, 1.0, 1.0)),
+                    Row::Volume | Row::Particles | Row::BoardMotion => {
+                        ("settings.png", Vec4::new(0.2, 0.8, 0.3, 1.0))
+                    }
+                    Row::Wallpaper => ("settings.png", Vec4::new(1.0, 0.6, 0.0, 1.0)),
+                    Row::BestOf => ("double.png", Vec4::new(1.0, 0.0, 1.0, 1.0)),
+                    Row::Rotation
+                    | Row::Ruleset
+                    | Row::AllSpin
+                    | Row::PerfectClear
+                    | Row::Width
+                    | Row::Height
+                    | Row::Randomizer(_)
+                    | Row::Preview => ("settings.png", Vec4::new(0.0, 0.8, 0.8, 1.0)),
+                    Row::Back => ("exit.png", Vec4::new(1.0, 0.15, 0.1, 1.0)),
+                };
+                MenuItem::new(
+                    Screen::Menu,
+                    String::new(),
+                    graphics::Texture::from_image(gh, &roman.get_image(icon)).unwrap(),
+                    color,
+                )
+            })
+            .collect();
+
+        let mut wallpapers = vec![Wallpaper::Random, Wallpaper::None];
+        wallpapers.extend(crate::game::wallpapers().into_iter().map(Wallpaper::File));
+
+        let mut me = Self {
+            menu: Menu::with_items(gh, roman, tr, items, None),
+            rows,
+            settings: Settings::load(),
+            profile: 0,
+            binding: None,
+            wallpapers,
+            rotations: crate::game::RotationSystem::available(),
+            rulesets: crate::game::Rules::available(),
+            done: false,
+        };
+        me.refresh();
+        me
+    }
+
+    /// Update the text of every item to the current values
+    fn refresh(&mut self) {
+        let settings = &self.settings;
+        let on_off = |x: bool| if x { "on" } else { "off" };
+        for (row, item) in self.rows.iter().zip(&mut self.menu.items) {
+            item.text = match row {
+                Row::Profile => format!("< {} >", Settings::profile_name(self.profile)),
+                Row::Handling(x) => x.text(&settings.handling[self.profile]),
+                Row::Key(x) if self.binding == Some(*x) => {
+                    format!("{}  press a key", x.name().replace('_', " "))
+                }
+                Row::Key(x) => {
+                    let codes = settings.keybinds[self.profile]
+                        .keycodes(*x)
+                        .into_iter()
+                        .map(crate::game::keycode_name)
+                        .collect::<Vec<&str>>();
+                    let mut text = format!(
+                        "{}  {}",
+                        x.name().replace('_', " "),
+                        if codes.is_empty() {
+                            String::from("-")
+                        } else {
+                            codes.join(" ")
+                        }
+                    );
+                    for other in settings.conflicts(self.profile, *x) {
+                        text += &format!("  (also P{})", other);
+                    }
+                    text
+                }
+                Row::Deadzone => format!(
+                    "stick deadzone  {}%",
+                    (settings.gamepad.deadzone * 100.0).round()
+                ),
+                Row::Rotation => format!("rotation system  {}", settings.rotation),
+                Row::Ruleset => format!("ruleset  {}", settings.rules.name),
+                Row::AllSpin => format!("all spins  {}", settings.rules.all_spin.name()),
+                Row::PerfectClear => {
+                    format!("perfect clears  {}", on_off(settings.rules.perfect_clear))
+                }
+                Row::Width => format!("board width  {}", settings.rules.width),
+                Row::Height => format!("board height  {}", settings.rules.height),
+                Row::Randomizer(goal) => {
+                    let randomizer = match goal {
+                        Some(x) => settings.single_randomizers[*x as usize],
+                        None => settings.split_randomizer,
+                    };
+                    format!(
+                        "{} pieces  {}",
+                        goal.map_or("split screen", |x| x.title()),
+                        randomizer.map_or("ruleset", |x| x.name())
+                    )
+                }
+                Row::Preview => format!("next pieces  {}", settings.rules.preview),
+                Row::BestOf => format!("split screen  best of {}", settings.best_of),
+                Row::Volume => format!("volume  {}%", (settings.volume * 100.0).round()),
+                Row::Particles => format!("particles  {}", on_off(settings.particles)),
+                Row::BoardMotion => format!("board motion  {}", on_off(settings.board_motion)),
+                Row::Wallpaper => format!("background  {}", settings.wallpaper.name()),
+                Row::Back => String::from("save and go back"),
+            };
+        }
+    }
+
+    /// Change the value of a row by `steps` steps
+    fn adjust(&mut self, row: Row, steps: i64) {
+        let settings = &mut self.settings;
+        match row {
+            Row::Profile => {
+                self.profile = (self.profile as i64 + steps).rem_euclid(PROFILES as i64) as usize
+            }
+            Row::Handling(x) => x.adjust(&mut settings.handling[self.profile], steps),
+            Row::BestOf => {
+                settings.best_of = (settings.best_of as i64 + steps * 2)
+                    .clamp(1, crate::game::MAX_BEST_OF as i64)
+                    as u32
+            }
+            Row::Volume => {
+                settings.volume =
+                    ((settings.volume * 10.0).round() + steps as f64).clamp(0.0, 10.0) / 10.0
+            }
+            Row::Deadzone => {
+                settings.gamepad.deadzone =
+                    ((settings.gamepad.deadzone * 20.0).round() + steps as f64).clamp(0.0, 19.0)
+                        / 20.0
+            }
+            Row::Rotation => {
+                let current = self
+                    .rotations
+                    .iter()
+                    .position(|x| *x == settings.rotation)
+                    .unwrap_or(0);
+                let next = (current as i64 + steps).rem_euclid(self.rotations.len() as i64);
+                settings.rotation = self.rotations[next as usize].clone();
+            }
+            Row::Ruleset => {
+                let current = self
+                    .rulesets
+                    .iter()
+                    .position(|x| *x == settings.rules.name)
+                    .unwrap_or(0);
+                let next = (current as i64 + steps).rem_euclid(self.rulesets.len() as i64);
+                let name = &self.rulesets[next as usize];
+                match crate::game::Rules::by_name(name) {
+                    Ok(x) => settings.rules = x,
+                    Err(e) => eprintln!("Unable to load ruleset {}: {}", name, e),
+                }
+            }
+            Row::AllSpin => {
+                let all = crate::game::AllSpin::ALL;
+                let current = all
+                    .iter()
+                    .position(|x| *x == settings.rules.all_spin)
+                    .unwrap_or(0);
+                let next = (current as i64 + steps).rem_euclid(all.len() as i64);
+                settings.rules.all_spin = all[next as usize];
+            }
+            Row::PerfectClear => settings.rules.perfect_clear = !settings.rules.perfect_clear,
+            Row::Randomizer(goal) => {
+                let randomizer = match goal {
+                    Some(x) => &mut settings.single_randomizers[x as usize],
+                    None => &mut settings.split_randomizer,
+                };
+                // the randomizer of the ruleset comes first
+                let all = crate::game::RandomizerKind::ALL;
+                let current = randomizer
+                    .and_then(|x| all.iter().position(|y| *y == x))
+                    .map_or(0, |x| x as i64 + 1);
+                let next = (current + steps).rem_euclid(all.len() as i64 + 1);
+                *randomizer = (next > 0).then(|| all[next as usize - 1]);
+            }
+            Row::Preview => {
+                settings.rules.preview = (settings.rules.preview as i64 + steps)
+                    .clamp(0, crate::game::Rules::MAX_PREVIEW as i64)
+                    as usize
+            }
+            Row::Width => {
+                let widths = crate::game::Rules::WIDTHS;
+                settings.rules.width = (settings.rules.width as i64 + steps)
+                    .clamp(*widths.start() as i64, *widths.end() as i64)
+                    as usize
+            }
+            Row::Height => {
+                let heights = crate::game::Rules::HEIGHTS;
+                settings.rules.height = (settings.rules.height as i64 + steps)
+                    .clamp(*heights.start() as i64, *heights.end() as i64)
+                    as usize
+            }
+            Row::Particles => settings.particles = !settings.particles,
+            Row::BoardMotion => settings.board_motion = !settings.board_motion,
+            Row::Wallpaper => {
+                let current = self
+                    .wallpapers
+                    .iter()
+                    .position(|x| *x == settings.wallpaper)
+                    .unwrap_or(0);
+                let next = (current as i64 + steps).rem_euclid(self.wallpapers.len() as i64);
+                settings.wallpaper = self.wallpapers[next as usize].clone();
+            }
+            Row::Key(_) | Row::Back => (),
+        }
+    }
+
+    /// Handle a key press, from the keyboard or from a gamepad
+    fn press(&mut self, x: glutin::event::VirtualKeyCode) {
+        use glutin::event::VirtualKeyCode;
+
+        let menu = &mut self.menu;
+        let row = self.rows[menu.active_item];
+        match x {
+            VirtualKeyCode::W | VirtualKeyCode::Up => {
+                menu.active_item = menu.active_item.saturating_sub(1);
+            }
+            VirtualKeyCode::S | VirtualKeyCode::Down => {
+                menu.active_item = (menu.active_item + 1).min(menu.items.len() - 1);
+            }
+            VirtualKeyCode::A | VirtualKeyCode::Left => self.adjust(row, -1),
+            VirtualKeyCode::D | VirtualKeyCode::Right => self.adjust(row, 1),
+            VirtualKeyCode::Space | VirtualKeyCode::Return => match row {
+                Row::Key(key) => self.binding = Some(key),
+                Row::Back => self.done = true,
+                row => self.adjust(row, 1),
+            },
+            VirtualKeyCode::Delete | VirtualKeyCode::Back => {
+                if let Row::Key(key) = row {
+                    self.settings.keybinds[self.profile].clear(key);
+                }
+            }
+            VirtualKeyCode::Escape => self.done = true,
+            _ => (),
+        }
+        self.refresh();
+    }
+}
+
+impl Playable for SettingsMenu {
+    fn update(&mut self) {
+        self.menu.update();
+    }
+
+    fn draw(&mut self, gh: &mut graphics::GraphicsHandle, screen_width: i32, screen_height: i32) {
+        self.menu.draw(gh, screen_width, screen_height);
+    }
+
+    fn input(&mut self, event: glutin::event::KeyboardInput) {
+        use glutin::event::VirtualKeyCode;
+
+        if let glutin::event::ElementState::Released = event.state {
+            return;
+        }
+        let x = match event.virtual_keycode {
+            Some(x) => x,
+            None => return,
+        };
+
+        if let Some(key) = self.binding.take() {
+            let keybinds = &mut self.settings.keybinds[self.profile];
+            if x != VirtualKeyCode::Escape {
+                if keybinds.keycodes(key).contains(&x) {
+                    // pressing a key which is already bound removes it
+                    keybinds.unbind(x);
+                } else {
+                    keybinds.bind(key, x);
+                }
+            }
+            self.refresh();
+            return;
+        }
+        self.press(x);
+    }
+
+    fn gamepad(&mut self, event: crate::gamepad::Event) {
+        let x = match event.menu_key() {
+            Some(x) => x,
+            None => return,
+        };
+        // only keyboard keys can be bound, but the gamepad can cancel
+        if self.binding.is_some() {
+            if x == glutin::event::VirtualKeyCode::Escape {
+                self.binding = None;
+                self.refresh();
+            }
+            return;
+        }
+        self.press(x);
+    }
+
+    fn next_screen(&mut self) -> Option<Screen> {
+        if !self.done {
+            return None;
+        }
+        if let Err(e) = self.settings.save() {
+            eprintln!("Unable to save settings: {}", e);
+        }
+        Some(Screen::Menu)
+    }
+}
@@ -0,0 +1,86 @@
+use super::protocol::*;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+/// A connection to the relay server.
+///
+/// The connection never blocks after it has been established, so it can be polled every tick.
+pub struct Client {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Client {
+    /// Connect to the relay at `address` and join `room`
+    pub fn connect(address: &str, room: &str, name: &str) -> std::io::Result<Self> {
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, DEFAULT_PORT)
+        };
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "unknown relay address"))?;
+        let stream = TcpStream::connect_timeout(&address, std::time::Duration::from_secs(3))?;
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        let mut me = Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        };
+        me.send(&Message::Join {
+            version: PROTOCOL_VERSION,
+            room: room.to_string(),
+            name: name.to_string(),
+        });
+        Ok(me)
+    }
+
+    /// Queue a message, it will be sent on the next poll
+    pub fn send(&mut self, message: &Message) {
+        self.outgoing.extend_from_slice(message.encode().as_bytes());
+    }
+
+    /// Send the queued messages and receive everything that has arrived
+    ///
+    /// # Return value
+    ///
+    /// Returns the received messages, or an error when the connection has been lost
+    pub fn poll(&mut self) -> std::io::Result<Vec<Message>> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                // the last messages of the relay, such as an error, come before the disconnect
+                Ok(0) if self.incoming.contains(&b'\n') => break,
+                Ok(0) => return Err(ErrorKind::ConnectionAborted.into()),
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut messages = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|x| *x == b'\n') {
+            let line = self.incoming.drain(..=end).collect::<Vec<u8>>();
+            if let Some(x) = Message::decode(&String::from_utf8_lossy(&line)) {
+                messages.push(x);
+            }
+        }
+        Ok(messages)
+    }
+}
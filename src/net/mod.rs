@@ -0,0 +1,22 @@
+mod client;
+pub mod protocol;
+
+pub use client::Client;
+pub use protocol::Message;
+
+/// The address of the relay server, taken from the `TETR_RELAY` environment variable
+pub fn relay_address() -> String {
+    std::env::var("TETR_RELAY").unwrap_or_else(|_| format!("127.0.0.1:{}", protocol::DEFAULT_PORT))
+}
+
+/// The room to join on the relay, taken from the `TETR_ROOM` environment variable
+pub fn room() -> String {
+    std::env::var("TETR_ROOM").unwrap_or_else(|_| String::from("default"))
+}
+
+/// The name shown to the opponent
+pub fn player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| String::from("player"))
+}
@@ -0,0 +1,90 @@
+//! The messages exchanged between the game and the relay server.
+//!
+//! Every message is a single line of JSON. This file is shared with the relay binary, so it must
+//! not depend on anything else in the game.
+
+/// Clients and relays with different protocol versions refuse to talk to each other
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The port the relay listens on when none is given
+pub const DEFAULT_PORT: u16 = 7777;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Client to relay: the first message, asking to join a room
+    Join {
+        version: u32,
+        room: String,
+        name: String,
+    },
+    /// Relay to client: the room is full and the match begins
+    Start { seed: u64, opponent: String },
+    /// Lines of garbage sent to the opponent
+    Garbage(u32),
+    /// The state of the sender's board, used for drawing the opponent
+    Snapshot(json::JsonValue),
+    /// The sender has topped out
+    ToppedOut,
+    /// Relay to client: the opponent has disconnected
+    Left,
+    /// Relay to client: the relay has refused the client
+    Error(String),
+}
+
+impl Message {
+    pub fn to_json(&self) -> json::JsonValue {
+        match self {
+            Message::Join {
+                version,
+                room,
+                name,
+            } => json::object! {
+                type: "join",
+                version: *version,
+                room: room.as_str(),
+                name: name.as_str(),
+            },
+            Message::Start { seed, opponent } => json::object! {
+                type: "start",
+                seed: *seed,
+                opponent: opponent.as_str(),
+            },
+            Message::Garbage(lines) => json::object! { type: "garbage", lines: *lines },
+            Message::Snapshot(board) => json::object! { type: "snapshot", board: board.clone() },
+            Message::ToppedOut => json::object! { type: "topped_out" },
+            Message::Left => json::object! { type: "left" },
+            Message::Error(message) => json::object! { type: "error", message: message.as_str() },
+        }
+    }
+
+    pub fn from_json(data: &json::JsonValue) -> Option<Self> {
+        Some(match data["type"].as_str()? {
+            "join" => Message::Join {
+                version: data["version"].as_u32()?,
+                room: data["room"].as_str()?.to_string(),
+                name: data["name"].as_str()?.to_string(),
+            },
+            "start" => Message::Start {
+                seed: data["seed"].as_u64()?,
+                opponent: data["opponent"].as_str()?.to_string(),
+            },
+            "garbage" => Message::Garbage(data["lines"].as_u32()?),
+            "snapshot" => Message::Snapshot(data["board"].clone()),
+            "topped_out" => Message::ToppedOut,
+            "left" => Message::Left,
+            "error" => Message::Error(data["message"].as_str()?.to_string()),
+            _ => return None,
+        })
+    }
+
+    /// Encode the message as a line, including the trailing newline
+    pub fn encode(&self) -> String {
+        let mut line = self.to_json().dump();
+        line.push('\n');
+        line
+    }
+
+    pub fn decode(line: &str) -> Option<Self> {
+        Self::from_json(&json::parse(line).ok()?)
+    }
+}
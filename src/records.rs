@@ -0,0 +1,246 @@
+use crate::game::{GameMode, Goal};
+use std::path::PathBuf;
+
+/// The most games kept in the history, older ones are forgotten
+const MAX_GAMES: usize = 1000;
+
+/// A game played by a single board
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    /// The name of the player, see [`crate::settings::Settings::names`]
+    pub player: String,
+    pub mode: GameMode,
+    /// When the game has ended, see [`crate::timestamp`]
+    pub date: String,
+    /// The length of the game in ticks
+    pub ticks: u64,
+    /// Whether the goal of a single player game has been reached
+    pub finished: bool,
+    pub score: u64,
+    pub lines: u32,
+    pub pps: f64,
+    pub apm: f64,
+    pub vs: f64,
+    pub kpp: f64,
+    pub max_combo: u32,
+    pub finesse_faults: u32,
+}
+
+impl GameRecord {
+    /// Whether the game counts as a personal best in its mode
+    fn ranked(&self) -> bool {
+        match self.mode {
+            // an unfinished sprint has no time to compare
+            GameMode::Single(Goal::Sprint) => self.finished,
+            GameMode::Single(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this game is better than `other` of the same mode
+    pub fn beats(&self, other: &GameRecord) -> bool {
+        match self.mode {
+            GameMode::Single(Goal::Sprint) => self.ticks < other.ticks,
+            _ => self.score > other.score,
+        }
+    }
+
+    /// What the game is ranked by, like the time of a sprint
+    pub fn result(&self) -> String {
+        match self.mode {
+            GameMode::Single(Goal::Sprint) => crate::game::format_time(self.ticks),
+            _ => format!("{} points", self.score),
+        }
+    }
+
+    fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            player: self.player.as_str(),
+            mode: self.mode.name(),
+            date: self.date.as_str(),
+            ticks: self.ticks,
+            finished: self.finished,
+            score: self.score,
+            lines: self.lines,
+            pps: self.pps,
+            apm: self.apm,
+            vs: self.vs,
+            kpp: self.kpp,
+            max_combo: self.max_combo,
+            finesse_faults: self.finesse_faults,
+        }
+    }
+
+    fn from_json(data: &json::JsonValue) -> Option<Self> {
+        Some(Self {
+            player: String::from(data["player"].as_str()?),
+            mode: GameMode::from_name(data["mode"].as_str()?)?,
+            date: String::from(data["date"].as_str()?),
+            ticks: data["ticks"].as_u64()?,
+            finished: data["finished"].as_bool()?,
+            score: data["score"].as_u64()?,
+            lines: data["lines"].as_u32()?,
+            pps: data["pps"].as_f64()?,
+            apm: data["apm"].as_f64()?,
+            vs: data["vs"].as_f64()?,
+            kpp: data["kpp"].as_f64()?,
+            max_combo: data["max_combo"].as_u32()?,
+            finesse_faults: data["finesse_faults"].as_u32()?,
+        })
+    }
+}
+
+/// The split screen matches won and lost by a player
+#[derive(Clone, Debug)]
+pub struct VersusRecord {
+    pub player: String,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// Every game played on this computer, stored in the data directory
+#[derive(Default)]
+pub struct Records {
+    /// The games, oldest first
+    pub games: Vec<GameRecord>,
+    /// Sorted by the name of the player
+    pub versus: Vec<VersusRecord>,
+}
+
+impl Records {
+    pub fn file() -> PathBuf {
+        crate::data_dir().join("records.json")
+    }
+
+    /// Load the records, invalid entries are skipped
+    pub fn load() -> Self {
+        let data = match std::fs::read_to_string(Self::file()) {
+            Ok(x) => x,
+            Err(_) => return Self::default(),
+        };
+        let data = match json::parse(&data) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Invalid records file {}: {}", Self::file().display(), e);
+                return Self::default();
+            }
+        };
+        Self {
+            games: data["games"]
+                .members()
+                .filter_map(GameRecord::from_json)
+                .collect(),
+            versus: data["versus"]
+                .members()
+                .filter_map(|x| {
+                    Some(VersusRecord {
+                        player: String::from(x["player"].as_str()?),
+                        wins: x["wins"].as_u32()?,
+                        losses: x["losses"].as_u32()?,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(crate::data_dir())?;
+        let data = json::object! {
+            games: self.games.iter().map(|x| x.to_json()).collect::<Vec<json::JsonValue>>(),
+            versus: self.versus.iter().map(|x| json::object! {
+                player: x.player.as_str(),
+                wins: x.wins,
+                losses: x.losses,
+            }).collect::<Vec<json::JsonValue>>(),
+        };
+        std::fs::write(Self::file(), data.pretty(4))?;
+        Ok(())
+    }
+
+    /// The best game of a player in a mode
+    pub fn best(&self, mode: GameMode, player: &str) -> Option<&GameRecord> {
+        self.games
+            .iter()
+            .filter(|x| x.mode == mode && x.player == player && x.ranked())
+            .fold(None, |best, x| match best {
+                Some(best) if !x.beats(best) => Some(best),
+                _ => Some(x),
+            })
+    }
+
+    /// The best game of every player in a mode, the best first
+    pub fn leaderboard(&self, mode: GameMode) -> Vec<&GameRecord> {
+        let mut players = self
+            .games
+            .iter()
+            .filter(|x| x.mode == mode)
+            .map(|x| x.player.as_str())
+            .collect::<Vec<&str>>();
+        players.sort();
+        players.dedup();
+        let mut best = players
+            .into_iter()
+            .filter_map(|x| self.best(mode, x))
+            .collect::<Vec<&GameRecord>>();
+        best.sort_by(|a, b| {
+            if a.beats(b) {
+                std::cmp::Ordering::Less
+            } else if b.beats(a) {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        });
+        best
+    }
+
+    /// Add a game to the history
+    ///
+    /// # Return value
+    ///
+    /// Returns whether the game is a new personal best of the player
+    pub fn add_game(&mut self, game: GameRecord) -> bool {
+        let best = game.ranked()
+            && self
+                .best(game.mode, &game.player)
+                .is_none_or(|best| game.beats(best));
+        self.games.push(game);
+        if self.games.len() > MAX_GAMES {
+            // keep the personal bests, they would be lost otherwise
+            if let Some(i) = (0..self.games.len()).find(|i| {
+                let x = &self.games[*i];
+                !self
+                    .best(x.mode, &x.player)
+                    .is_some_and(|best| std::ptr::eq(best, x))
+            }) {
+                self.games.remove(i);
+            }
+        }
+        best
+    }
+
+    /// Count a split screen match for every player, `winner` being the one left standing
+    pub fn add_match(&mut self, players: &[String], winner: &str) {
+        for player in players {
+            let i = match self.versus.binary_search_by(|x| x.player.cmp(player)) {
+                Ok(i) => i,
+                Err(i) => {
+                    self.versus.insert(
+                        i,
+                        VersusRecord {
+                            player: player.clone(),
+                            wins: 0,
+                            losses: 0,
+                        },
+                    );
+                    i
+                }
+            };
+            if player == winner {
+                self.versus[i].wins += 1;
+            } else {
+                self.versus[i].losses += 1;
+            }
+        }
+    }
+}
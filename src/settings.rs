@@ -0,0 +1,223 @@
+use crate::game::{Goal, Handling, KeyBinds, RandomizerKind, Rules};
+use crate::gamepad::GamepadBinds;
+use std::path::PathBuf;
+
+/// The number of profiles: the single player one, followed by one for every split screen player
+pub const PROFILES: usize = crate::game::MAX_PLAYERS + 1;
+
+/// The wallpaper shown behind the game
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Wallpaper {
+    /// A random picture from the `backgrounds` directory
+    Random,
+    /// No picture at all
+    None,
+    /// The given file in the `backgrounds` directory
+    File(String),
+}
+
+impl Wallpaper {
+    /// The name of the wallpaper as stored in files
+    pub fn name(&self) -> &str {
+        match self {
+            Wallpaper::Random => "random",
+            Wallpaper::None => "none",
+            Wallpaper::File(x) => x,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "random" => Wallpaper::Random,
+            "none" => Wallpaper::None,
+            x => Wallpaper::File(String::from(x)),
+        }
+    }
+}
+
+/// Everything the player can configure, stored in the config directory
+pub struct Settings {
+    /// The handling of every profile. The single player profile is also used online.
+    pub handling: Vec<Handling>,
+    /// The keys of every profile
+    pub keybinds: Vec<KeyBinds>,
+    /// The name of the player of every profile, the records are kept by it
+    pub names: Vec<String>,
+    /// The buttons of all the gamepads
+    pub gamepad: GamepadBinds,
+    /// The name of the rotation system, see [`crate::game::RotationSystem::by_name`]
+    pub rotation: String,
+    /// The rules of single player and split screen games
+    pub rules: Rules,
+    /// The randomizer of the single player games of every goal, in the order of [`Goal::ALL`],
+    /// `None` for the one of the rules
+    pub single_randomizers: [Option<RandomizerKind>; Goal::ALL.len()],
+    /// The randomizer of split screen games, `None` for the one of the rules
+    pub split_randomizer: Option<RandomizerKind>,
+    /// The length of split screen series, 1 for single games
+    pub best_of: u32,
+    /// The volume of the sound effects, from 0 to 1
+    pub volume: f64,
+    /// Whether pieces, line clears and hard drops throw particles around
+    pub particles: bool,
+    /// Whether the boards bounce and shake when something happens on them
+    pub board_motion: bool,
+    pub wallpaper: Wallpaper,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            handling: vec![Handling::default(); PROFILES],
+            keybinds: KeyBinds::defaults(),
+            names: (0..PROFILES)
+                .map(|x| match x {
+                    0 => String::from("player"),
+                    x => format!("P{}", x),
+                })
+                .collect(),
+            gamepad: GamepadBinds::default(),
+            rotation: String::from("srs+"),
+            rules: Rules::default(),
+            single_randomizers: [None; Goal::ALL.len()],
+            split_randomizer: None,
+            best_of: 1,
+            volume: 1.0,
+            particles: true,
+            board_motion: true,
+            wallpaper: Wallpaper::Random,
+        }
+    }
+}
+
+impl Settings {
+    pub fn file() -> PathBuf {
+        crate::config_dir().join("settings.json")
+    }
+
+    /// The name of a profile shown to the player
+    pub fn profile_name(profile: usize) -> String {
+        match profile {
+            0 => String::from("single player"),
+            x => format!("split screen P{}", x),
+        }
+    }
+
+    /// The other split screen profiles which use one of the keyboard keys bound to `key` in
+    /// `profile`. Those players share the keyboard, so the key would do two things at once.
+    pub fn conflicts(&self, profile: usize, key: crate::game::Key) -> Vec<usize> {
+        if profile == 0 {
+            return Vec::new();
+        }
+        let codes = self.keybinds[profile].keycodes(key);
+        (1..PROFILES)
+            .filter(|x| *x != profile)
+            .filter(|x| {
+                self.keybinds[profile]
+                    .conflicts(&self.keybinds[*x])
+                    .iter()
+                    .any(|code| codes.contains(code))
+            })
+            .collect()
+    }
+
+    /// Load the settings file, anything missing or invalid is left at the default
+    pub fn load() -> Self {
+        let mut settings = Self::default();
+        let data = match std::fs::read_to_string(Self::file()) {
+            Ok(x) => x,
+            Err(_) => {
+                // write the defaults, so that there is a file to edit
+                if let Err(e) = settings.save() {
+                    eprintln!("Unable to save settings: {}", e);
+                }
+                return settings;
+            }
+        };
+        let data = match json::parse(&data) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Invalid settings file {}: {}", Self::file().display(), e);
+                return settings;
+            }
+        };
+        for (handling, data) in settings.handling.iter_mut().zip(data["handling"].members()) {
+            *handling = Handling::from_json(data);
+        }
+        for (keybinds, data) in settings.keybinds.iter_mut().zip(data["keybinds"].members()) {
+            *keybinds = KeyBinds::from_json(data, keybinds);
+        }
+        for (name, data) in settings.names.iter_mut().zip(data["names"].members()) {
+            if let Some(x) = data.as_str() {
+                *name = String::from(x);
+            }
+        }
+        if data.has_key("gamepad") {
+            settings.gamepad = GamepadBinds::from_json(&data["gamepad"]);
+        }
+        if let Some(x) = data["rotation"].as_str() {
+            settings.rotation = String::from(x);
+        }
+        if data.has_key("rules") {
+            match Rules::from_json(
+                data["rules"]["name"].as_str().unwrap_or("tetr.io"),
+                &data["rules"],
+            ) {
+                Ok(x) => settings.rules = x,
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        for goal in Goal::ALL {
+            if let Some(x) = data["single_randomizers"][goal.name()].as_str() {
+                settings.single_randomizers[goal as usize] = RandomizerKind::from_name(x);
+            }
+        }
+        if let Some(x) = data["split_randomizer"].as_str() {
+            settings.split_randomizer = RandomizerKind::from_name(x);
+        }
+        if let Some(x) = data["best_of"].as_u32() {
+            // only odd lengths always have a winner
+            settings.best_of = (x | 1).min(crate::game::MAX_BEST_OF);
+        }
+        if let Some(x) = data["volume"].as_f64() {
+            settings.volume = x.clamp(0.0, 1.0);
+        }
+        if let Some(x) = data["particles"].as_bool() {
+            settings.particles = x;
+        }
+        if let Some(x) = data["board_motion"].as_bool() {
+            settings.board_motion = x;
+        }
+        if let Some(x) = data["wallpaper"].as_str() {
+            settings.wallpaper = Wallpaper::from_name(x);
+        }
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(crate::config_dir())?;
+        let mut single_randomizers = json::JsonValue::new_object();
+        for goal in Goal::ALL {
+            single_randomizers[goal.name()] = self.single_randomizers[goal as usize]
+                .map(|x| x.name())
+                .into();
+        }
+        let data = json::object! {
+            handling: self.handling.iter().map(|x| x.to_json()).collect::<Vec<json::JsonValue>>(),
+            keybinds: self.keybinds.iter().map(|x| x.to_json()).collect::<Vec<json::JsonValue>>(),
+            names: self.names.clone(),
+            gamepad: self.gamepad.to_json(),
+            rotation: self.rotation.as_str(),
+            rules: self.rules.to_json(),
+            single_randomizers: single_randomizers,
+            split_randomizer: self.split_randomizer.map(|x| x.name()),
+            best_of: self.best_of,
+            volume: self.volume,
+            particles: self.particles,
+            board_motion: self.board_motion,
+            wallpaper: self.wallpaper.name(),
+        };
+        std::fs::write(Self::file(), data.pretty(4))?;
+        Ok(())
+    }
+}
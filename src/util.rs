@@ -1,11 +1,35 @@
+/// The rate at which [`Playable::update`] is called, independently of the frame rate
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// Convert a duration in milliseconds into a whole number of ticks
+pub const fn ms_to_ticks(ms: u64) -> u64 {
+    ms * TICKS_PER_SECOND as u64 / 1000
+}
+
 pub enum Screen {
     Menu,
-    SingleGame,
-    DoubleGame,
+    /// Choosing the goal of a single player game
+    SingleMenu,
+    SingleGame(crate::game::Goal),
+    /// Choosing the number of players for split screen
+    SplitMenu,
+    /// A split screen game with the given number of players
+    SplitGame(usize),
+    /// The next split screen game of a series
+    Rematch(crate::game::Series),
+    OnlineGame,
+    Settings,
+    Replays,
+    Replay(std::path::PathBuf),
+    /// The personal bests, the split screen matches and the history of the games
+    Records,
+    /// What has been achieved in the game which has just ended
+    Results(crate::game::Results),
     Exit,
 }
 
 pub trait Playable {
+    /// Advance the screen by one tick, this is called [`TICKS_PER_SECOND`] times per second
     fn update(&mut self);
     fn draw(
         &mut self,
@@ -14,7 +38,61 @@ pub trait Playable {
         screen_height: i32,
     );
     fn input(&mut self, event: glutin::event::KeyboardInput);
+    /// Handle something that happened on a gamepad, most screens ignore them
+    fn gamepad(&mut self, _event: crate::gamepad::Event) {}
     fn next_screen(&mut self) -> Option<Screen> {
         None
     }
 }
+
+/// The directory where the game keeps its data, like replays
+pub fn data_dir() -> std::path::PathBuf {
+    if let Some(x) = std::env::var_os("XDG_DATA_HOME") {
+        std::path::PathBuf::from(x).join("tetr")
+    } else if let Some(x) = std::env::var_os("APPDATA") {
+        std::path::PathBuf::from(x).join("tetr")
+    } else if let Some(x) = std::env::var_os("HOME") {
+        std::path::PathBuf::from(x).join(".local/share/tetr")
+    } else {
+        std::path::PathBuf::from("data")
+    }
+}
+
+/// The directory where the game keeps its configuration, like the settings
+pub fn config_dir() -> std::path::PathBuf {
+    if let Some(x) = std::env::var_os("XDG_CONFIG_HOME") {
+        std::path::PathBuf::from(x).join("tetr")
+    } else if let Some(x) = std::env::var_os("APPDATA") {
+        std::path::PathBuf::from(x).join("tetr")
+    } else if let Some(x) = std::env::var_os("HOME") {
+        std::path::PathBuf::from(x).join(".config/tetr")
+    } else {
+        std::path::PathBuf::from("config")
+    }
+}
+
+/// The current UTC time formatted as `YYYY-MM-DD_hh-mm-ss`, sortable and usable in file names
+pub fn timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+    // convert days since epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}